@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+use dioxus::prelude::*;
+
+use crate::{
+    book_text, dict_index,
+    read_state::ReaderState,
+    scan,
+    segment::{self, Token},
+    user_db::{self, BookProfile},
+};
+
+/// The text of a chapter split into dictionary words.
+pub struct ChapterAnalysis {
+    pub page: usize,
+    pub title: String,
+    /// The text as characters, since it is mostly indexed by character offsets
+    pub chars: Vec<char>,
+    pub tokens: Vec<Token>,
+}
+
+impl ChapterAnalysis {
+    /// The sentence around the character `offset`.
+    pub fn sentence_at(&self, offset: usize) -> String {
+        let (start, end) = scan::sentence_bounds(&self.chars, offset);
+
+        self.chars[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+/// Every chapter of a book split into dictionary words.
+pub struct BookAnalysis {
+    pub book: String,
+    pub chapters: Vec<ChapterAnalysis>,
+    /// Frequency ranks of the words in the book
+    pub ranks: HashMap<String, u64>,
+}
+
+/// How often a word occurs, and where it does first.
+pub struct WordStats {
+    pub expression: String,
+    pub reading: String,
+    pub count: usize,
+    pub first_page: usize,
+    pub first_offset: usize,
+}
+
+impl BookAnalysis {
+    pub fn chapter(&self, page: usize) -> Option<&ChapterAnalysis> {
+        self.chapters.iter().find(|c| c.page == page)
+    }
+
+    /// The sentence a word first occurs in.
+    pub fn first_sentence(&self, word: &WordStats) -> String {
+        self.chapter(word.first_page)
+            .map(|c| c.sentence_at(word.first_offset))
+            .unwrap_or_default()
+    }
+
+    /// Words in the whole book or only the chapter `page`, most frequent first.
+    pub fn vocabulary(&self, page: Option<usize>) -> Vec<WordStats> {
+        let mut words: HashMap<&str, WordStats> = HashMap::new();
+
+        let chapters = self
+            .chapters
+            .iter()
+            .filter(|c| page.map_or(true, |page| c.page == page));
+        for chapter in chapters {
+            for token in &chapter.tokens {
+                words
+                    .entry(token.expression.as_str())
+                    .and_modify(|w| w.count += 1)
+                    .or_insert_with(|| WordStats {
+                        expression: token.expression.clone(),
+                        reading: token.reading.clone(),
+                        count: 1,
+                        first_page: chapter.page,
+                        first_offset: token.offset,
+                    });
+            }
+        }
+
+        let mut words = words.into_values().collect::<Vec<_>>();
+        words.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then((a.first_page, a.first_offset).cmp(&(b.first_page, b.first_offset)))
+        });
+
+        words
+    }
+}
+
+/// Progress of analysing the open book.
+pub enum AnalysisState {
+    Idle,
+    /// The chapter being analysed and the number of chapters
+    Running(usize, usize),
+    Done(BookAnalysis),
+}
+
+/// Starts analysing the open book in the background.
+pub fn start(
+    read_state: &UseRef<Option<ReaderState>>,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &UseState<yomi_dict::Reasons>,
+    known: &UseRef<HashSet<String>>,
+    state: &UseRef<AnalysisState>,
+) {
+    let read_state = read_state.clone();
+    let db = db.clone();
+    let reasons = reasons.clone();
+    let known = known.clone();
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        analyze_book(&read_state, &db, reasons.get(), &known, &state).await;
+    });
+}
+
+/// Splits every chapter of the open book into words, updating `state` as it goes.
+pub async fn analyze_book(
+    read_state: &UseRef<Option<ReaderState>>,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+    known: &UseRef<HashSet<String>>,
+    state: &UseRef<AnalysisState>,
+) {
+    if db.read().is_none() {
+        log::error!("Cannot analyze book since DB is not loaded yet!");
+        return;
+    }
+
+    let Some((book, page_count)) =
+        read_state.with(|state| state.as_ref().map(|s| (s.get_title(), s.get_page_count())))
+    else {
+        return;
+    };
+
+    let mut chapters = Vec::with_capacity(page_count);
+    for page in 0..page_count {
+        state.set(AnalysisState::Running(page, page_count));
+
+        // Reading other chapters doesn't change what is shown, so nothing needs to rerender
+        let chapter = read_state.write_silent().as_mut().and_then(|state| {
+            let html = state.get_chapter_str(page)?;
+            let title = state
+                .get_chapter_title(page)
+                .unwrap_or_else(|| format!("Chapter {}", page + 1));
+            Some((html, title))
+        });
+        let Some((html, title)) = chapter else {
+            log::warn!("Cannot read chapter {page}");
+            continue;
+        };
+
+        let text = book_text::plain_text(&html);
+        let tokens = match segment::segment(&text, reasons, db, |_, _| {}).await {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                log::error!("Cannot analyze chapter {page} due to error {e}");
+                state.set(AnalysisState::Idle);
+                return;
+            }
+        };
+
+        chapters.push(ChapterAnalysis {
+            page,
+            title,
+            chars: text.chars().collect(),
+            tokens,
+        });
+    }
+
+    let expressions = chapters
+        .iter()
+        .flat_map(|c| c.tokens.iter().map(|t| t.expression.as_str()))
+        .collect::<HashSet<_>>();
+    let ranks = dict_index::frequency_ranks(expressions)
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Cannot get word frequencies due to error {e}");
+            HashMap::new()
+        });
+
+    let analysis = BookAnalysis {
+        book,
+        chapters,
+        ranks,
+    };
+
+    // Remember the profile, so books can be compared without analysing them again
+    let profile = BookProfile {
+        book: analysis.book.clone(),
+        chapters: analysis.chapters.len(),
+        characters: analysis.chapters.iter().map(|c| c.chars.len()).sum(),
+        difficulty: analysis.book_difficulty(&known.read()),
+        timestamp: js_sys::Date::now(),
+    };
+    if let Err(e) = user_db::put_book_profile(&profile).await {
+        log::error!("Cannot save profile of {} due to error {e}", profile.book);
+    }
+
+    state.set(AnalysisState::Done(analysis));
+}
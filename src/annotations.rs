@@ -0,0 +1,331 @@
+use dioxus::prelude::*;
+
+use crate::{
+    export, highlight, notes_export,
+    read_state::ReaderState,
+    scan::{self, SelectedText},
+    upload_component::upload_component,
+    user_db::{self, Annotation, Color},
+};
+
+impl Color {
+    pub const ALL: [Self; 4] = [Self::Yellow, Self::Green, Self::Blue, Self::Pink];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Yellow => "Yellow",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+            Self::Pink => "Pink",
+        }
+    }
+
+    /// Name of the highlight the text is shown with, styled in tailwind.css.
+    const fn highlight(self) -> &'static str {
+        match self {
+            Self::Yellow => "yomi-annotation-yellow",
+            Self::Green => "yomi-annotation-green",
+            Self::Blue => "yomi-annotation-blue",
+            Self::Pink => "yomi-annotation-pink",
+        }
+    }
+
+    pub const fn swatch(self) -> &'static str {
+        match self {
+            Self::Yellow => "bg-yellow-200 hover:bg-yellow-300",
+            Self::Green => "bg-green-200 hover:bg-green-300",
+            Self::Blue => "bg-blue-200 hover:bg-blue-300",
+            Self::Pink => "bg-pink-200 hover:bg-pink-300",
+        }
+    }
+}
+
+/// Saves text selected in the open chapter as an annotation without a note.
+pub async fn annotate(
+    read_state: &UseRef<Option<ReaderState>>,
+    selected: SelectedText,
+    color: Color,
+) {
+    let Some((book, chapter)) =
+        read_state.with(|state| state.as_ref().map(|s| (s.get_title(), s.get_page())))
+    else {
+        return;
+    };
+
+    let annotation = Annotation {
+        id: None,
+        book,
+        chapter,
+        offset: selected.offset,
+        len: selected.len,
+        text: selected.text,
+        color,
+        note: String::new(),
+        timestamp: js_sys::Date::now(),
+    };
+
+    if let Err(e) = user_db::add_annotation(&annotation).await {
+        log::error!("Cannot add annotation due to error {e}");
+    }
+}
+
+/// Highlights the annotations of the open chapter, which must be rendered already.
+pub async fn apply(read_state: &UseRef<Option<ReaderState>>) {
+    let Some((book, chapter)) =
+        read_state.with(|state| state.as_ref().map(|s| (s.get_title(), s.get_page())))
+    else {
+        return;
+    };
+
+    let annotations = match user_db::get_annotations(&book).await {
+        Ok(annotations) => annotations,
+        Err(e) => {
+            log::error!("Cannot load annotations due to error {e}");
+            return;
+        }
+    };
+
+    for color in Color::ALL {
+        let spans = annotations
+            .iter()
+            .filter(|a| a.chapter == chapter && a.color == color)
+            .map(|a| (a.offset, a.len))
+            .collect::<Vec<_>>();
+
+        if spans.is_empty() {
+            highlight::clear(color.highlight());
+        } else {
+            highlight::set(
+                color.highlight(),
+                &scan::offset_ranges(scan::READER_CONTENT_ID, &spans),
+            );
+        }
+    }
+}
+
+#[inline_props]
+fn annotation_item<'a>(
+    cx: Scope,
+    annotation: &'a Annotation,
+    chapter_title: String,
+    read_state: &'a UseRef<Option<ReaderState>>,
+    revision: &'a UseState<u32>,
+) -> Element {
+    let note = use_state(cx, || annotation.note.clone());
+
+    let id = annotation.id.unwrap_or_default();
+    let (chapter, offset) = (annotation.chapter, annotation.offset);
+    let swatch = annotation.color.swatch();
+    let date = export::format_timestamp(annotation.timestamp);
+    let unchanged = *note.get() == annotation.note;
+
+    cx.render(rsx!(li{
+        class: "border-b py-1",
+
+        blockquote{
+            class: "px-1 rounded {swatch}",
+
+            "{annotation.text}"
+        }
+
+        p{
+            class: "text-sm text-gray-600",
+
+            "{chapter_title}, {date}"
+        }
+
+        textarea{
+            class: "w-full bg-gray-100 rounded p-1 mt-1",
+            placeholder: "Note",
+            rows: "2",
+            value: "{note}",
+            oninput: move |evt: FormEvent| note.set(evt.value.clone()),
+        }
+
+        button{
+            class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300 disabled:opacity-50",
+            disabled: "{unchanged}",
+
+            onclick: move |_| {
+                let annotation = Annotation {
+                    note: note.get().trim().to_string(),
+                    ..(*annotation).clone()
+                };
+                let revision = (*revision).clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = user_db::put_annotation(&annotation).await {
+                        log::error!("Cannot save note due to error {e}");
+                    }
+                    revision.modify(|r| r.wrapping_add(1));
+                });
+            },
+
+            "Save note"
+        }
+
+        button{
+            class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+            onclick: move |_| read_state.with_mut(|state| {
+                if let Some(state) = state.as_mut() {
+                    state.jump_to(chapter, Some(offset));
+                }
+            }),
+
+            "Go to"
+        }
+
+        button{
+            class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+            onclick: move |_| {
+                let revision = (*revision).clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = user_db::delete_annotation(id).await {
+                        log::error!("Cannot remove annotation due to error {e}");
+                    }
+                    revision.modify(|r| r.wrapping_add(1));
+                });
+            },
+
+            "Remove"
+        }
+    }))
+}
+
+#[derive(Props)]
+pub struct AnnotationsProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+    /// Bumped whenever annotations change, so they are reloaded and highlighted again
+    revision: &'a UseState<u32>,
+}
+
+/// Exports the bookmarks and annotations of `book`, as Markdown or otherwise as JSON.
+fn export_notes(read_state: &UseRef<Option<ReaderState>>, book: String, as_markdown: bool) {
+    let chapter_titles = read_state.with(|state| {
+        state.as_ref().map_or_else(Vec::new, |s| {
+            (0..s.get_page_count())
+                .map(|page| {
+                    s.get_chapter_title(page)
+                        .unwrap_or_else(|| format!("Chapter {}", page + 1))
+                })
+                .collect()
+        })
+    });
+
+    wasm_bindgen_futures::spawn_local(async move {
+        match notes_export::load(&book).await {
+            Ok(notes) if as_markdown => notes_export::export_markdown(&notes, &chapter_titles),
+            Ok(notes) => notes_export::export_json(&notes),
+            Err(e) => log::error!("Cannot export notes due to error {e}"),
+        }
+    });
+}
+
+/// Every annotation in the open book.
+pub fn annotations_component<'a>(cx: Scope<'a, AnnotationsProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+    let revision = cx.props.revision;
+
+    let import_status = use_state(cx, || None::<String>);
+
+    let book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let annotations = use_future(cx, (&book, revision.get()), |(book, _)| async move {
+        match book {
+            Some(book) => user_db::get_annotations(&book).await,
+            None => Ok(Vec::new()),
+        }
+    });
+
+    let Some(book) = book else {
+        return cx.render(rsx!(p{ class: "mt-2", "No document" }));
+    };
+
+    let annotations = match annotations.value() {
+        None => return cx.render(rsx!(p{"Loading annotations"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load annotations: {e}"}));
+        }
+        Some(Ok(annotations)) => annotations,
+    };
+
+    let count = annotations.len();
+    let summary = if annotations.is_empty() {
+        "No annotations in this book yet. Select some text in the book to highlight it.".to_string()
+    } else {
+        format!("{count} annotations")
+    };
+
+    let markdown_book = book.clone();
+    let json_book = book;
+    let revision_moved = revision.clone();
+    let import_status_moved = import_status.clone();
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-wrap items-center gap-2 mt-2",
+
+            p{
+                class: "flex-1 text-sm text-gray-600",
+
+                "{summary}"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_notes(read_state, markdown_book.clone(), true),
+
+                "Export Markdown"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_notes(read_state, json_book.clone(), false),
+
+                "Back up as JSON"
+            }
+
+            upload_component{
+                label: "Restore from JSON",
+                id: "notes-upload",
+                upload_callback: move |data: Vec<u8>| {
+                    let revision = revision_moved.clone();
+                    let import_status = import_status_moved.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let status = match notes_export::import(&data).await {
+                            Ok(added) => format!("Restored {added} bookmarks and annotations"),
+                            Err(e) => format!("Cannot restore notes: {e}"),
+                        };
+                        import_status.set(Some(status));
+                        revision.modify(|r| r.wrapping_add(1));
+                    });
+                },
+            }
+        }
+
+        import_status.get().as_ref().map(|status| rsx!(p{ class: "text-sm", "{status}" }))
+
+        ul{
+            class: "list-none",
+
+            annotations.iter().map(|annotation| {
+                let id = annotation.id.unwrap_or_default();
+                let chapter_title = read_state
+                    .with(|state| state.as_ref().and_then(|s| s.get_chapter_title(annotation.chapter)))
+                    .unwrap_or_else(|| format!("Chapter {}", annotation.chapter + 1));
+
+                rsx!(annotation_item{
+                    key: "{id}",
+                    annotation: annotation,
+                    chapter_title: chapter_title,
+                    read_state: read_state,
+                    revision: revision,
+                })
+            })
+        }
+    })
+}
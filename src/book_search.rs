@@ -0,0 +1,230 @@
+use dioxus::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+use crate::{book_text, read_state::ReaderState};
+
+// Characters shown on either side of a match
+const CONTEXT_CHARS: usize = 30;
+// Common words match thousands of times, which is too many to render
+const RESULT_LIMIT: usize = 500;
+
+/// A match of the search in the open book.
+pub struct SearchResult {
+    pub page: usize,
+    pub chapter_title: String,
+    /// Character offset and length of the match in the text of the chapter
+    pub offset: usize,
+    pub len: usize,
+    /// Text before, of and after the match
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+/// Builds the pattern for `query`, which is taken literally unless `is_regex`.
+pub fn pattern(query: &str, is_regex: bool) -> Result<Regex, regex::Error> {
+    let pattern = if is_regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    RegexBuilder::new(&pattern).case_insensitive(true).build()
+}
+
+/// Finds `pattern` in every chapter of the book, in reading order, stopping after `limit` matches.
+/// Markup and furigana are ignored, so text split by ruby still matches.
+pub fn find(state: &mut ReaderState, pattern: &Regex, limit: usize) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+
+    for page in 0..state.get_page_count() {
+        let Some(html) = state.get_chapter_str(page) else {
+            log::warn!("Cannot read chapter {page}");
+            continue;
+        };
+        let chapter_title = state
+            .get_chapter_title(page)
+            .unwrap_or_else(|| format!("Chapter {}", page + 1));
+        let text = book_text::plain_text(&html);
+
+        // The reader counts characters, while matches are in bytes
+        let mut offset = 0;
+        let mut last_end = 0;
+        for m in pattern.find_iter(&text) {
+            // Patterns like `a*` match nothing everywhere
+            if m.as_str().is_empty() {
+                continue;
+            }
+
+            offset += text[last_end..m.start()].chars().count();
+            let len = m.as_str().chars().count();
+
+            let mut before = text[..m.start()]
+                .chars()
+                .rev()
+                .take(CONTEXT_CHARS)
+                .collect::<Vec<_>>();
+            before.reverse();
+
+            results.push(SearchResult {
+                page,
+                chapter_title: chapter_title.clone(),
+                offset,
+                len,
+                before: before.into_iter().collect(),
+                matched: m.as_str().to_string(),
+                after: text[m.end()..].chars().take(CONTEXT_CHARS).collect(),
+            });
+
+            if results.len() >= limit {
+                return results;
+            }
+
+            offset += len;
+            last_end = m.end();
+        }
+    }
+
+    results
+}
+
+#[derive(Props)]
+pub struct BookSearchProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+}
+
+/// Searches the text of the whole open book.
+pub fn book_search_component<'a>(cx: Scope<'a, BookSearchProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+
+    let query = use_state(cx, String::new);
+    let is_regex = use_state(cx, || false);
+    // None until something was searched for
+    let results = use_ref(cx, || None::<Result<Vec<SearchResult>, String>>);
+
+    let has_document = read_state.read().is_some();
+    if !has_document {
+        return cx.render(rsx!(p{ class: "mt-2", "No document" }));
+    }
+
+    let placeholder = if *is_regex.get() {
+        "Regular expression, like 思[いっ]"
+    } else {
+        "Text to find in the book"
+    };
+
+    let results_ref = results.read();
+    let listing = match results_ref.as_ref() {
+        None => None,
+        Some(Err(e)) => Some(rsx!(p{ class: "text-sm mt-2", "Invalid pattern: {e}" })),
+        Some(Ok(found)) => {
+            let count = found.len();
+            let summary = match count {
+                0 => "No matches".to_string(),
+                RESULT_LIMIT => format!("Showing the first {count} matches"),
+                _ => format!("{count} matches"),
+            };
+
+            Some(rsx! {
+                p{
+                    class: "text-sm text-gray-600 mt-2",
+
+                    "{summary}"
+                }
+
+                ul{
+                    class: "list-none",
+
+                    found.iter().map(|result| {
+                        let (page, offset, len) = (result.page, result.offset, result.len);
+
+                        rsx!(li{
+                            key: "{page}-{offset}",
+                            class: "border-b",
+
+                            button{
+                                class: "w-full text-left py-1 hover:bg-gray-100",
+
+                                onclick: move |_| read_state.with_mut(|state| {
+                                    if let Some(state) = state.as_mut() {
+                                        state.jump_to_match(page, offset, len);
+                                    }
+                                }),
+
+                                p{
+                                    "{result.before}"
+                                    mark{
+                                        class: "bg-yellow-200 rounded",
+
+                                        "{result.matched}"
+                                    }
+                                    "{result.after}"
+                                }
+
+                                span{
+                                    class: "text-sm text-gray-600",
+
+                                    "{result.chapter_title}"
+                                }
+                            }
+                        })
+                    })
+                }
+            })
+        }
+    };
+
+    cx.render(rsx! {
+        form{
+            class: "flex gap-2 mt-2",
+
+            prevent_default: "onsubmit",
+            onsubmit: move |_| {
+                let text = query.get().trim().to_string();
+                if text.is_empty() {
+                    return;
+                }
+
+                let found = match pattern(&text, *is_regex.get()) {
+                    // Reading other chapters doesn't change what is shown, so nothing needs to rerender
+                    Ok(pattern) => Ok(read_state
+                        .write_silent()
+                        .as_mut()
+                        .map_or_else(Vec::new, |state| find(state, &pattern, RESULT_LIMIT))),
+                    Err(e) => Err(e.to_string()),
+                };
+                results.set(Some(found));
+            },
+
+            input{
+                class: "flex-1 min-w-0 px-2 rounded border-2",
+
+                r#type: "search",
+                placeholder: "{placeholder}",
+                value: "{query}",
+                oninput: move |evt: FormEvent| query.set(evt.value.clone()),
+            }
+
+            label{
+                class: "flex items-center gap-1 text-sm",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{is_regex}",
+                    onchange: move |evt: FormEvent| is_regex.set(evt.value == "true"),
+                }
+
+                "Regex"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+                r#type: "submit",
+
+                "Find"
+            }
+        }
+
+        listing
+    })
+}
@@ -0,0 +1,113 @@
+thread_local! {
+    static BODY: regex::Regex = regex::Regex::new(r"<body[^>]*>([\s\S]*)</body>").unwrap();
+    // Furigana and anything else that isn't text is dropped along with its content
+    static HIDDEN: regex::Regex = regex::Regex::new(
+        r"(?is)<!--.*?-->|<rt[\s>].*?</rt>|<rp[\s>].*?</rp>|<script[\s>].*?</script>|<style[\s>].*?</style>",
+    )
+    .unwrap();
+    static TAG: regex::Regex = regex::Regex::new(r"<[^>]*>").unwrap();
+    static ENTITY: regex::Regex = regex::Regex::new(r"&(#?[0-9A-Za-z]+);").unwrap();
+}
+
+/// Turns the XHTML of a chapter into the text a reader sees, without markup or furigana.
+/// Offsets in the result match those counted in the rendered chapter, which leaves out the same
+/// furigana, scripts and styles, and has line breaks normalized like the browser does.
+///
+/// Only common named entities are decoded, others are counted as written.
+pub fn plain_text(html: &str) -> String {
+    // The browser turns every line break into a line feed while parsing
+    let html = html.replace("\r\n", "\n").replace('\r', "\n");
+    let html = html.as_str();
+
+    let body = BODY.with(|body| {
+        body.captures(html)
+            .and_then(|caps| caps.get(1))
+            .map_or(html, |m| m.as_str())
+    });
+
+    let text = HIDDEN.with(|hidden| hidden.replace_all(body, ""));
+    let text = TAG.with(|tag| tag.replace_all(&text, "").into_owned());
+
+    decode_entities(&text)
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "middot" => '·',
+        "times" => '×',
+        "copy" => '©',
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            return char::from_u32(code);
+        }
+    };
+
+    Some(c)
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY.with(|entity| {
+        entity
+            .replace_all(text, |caps: &regex::Captures| {
+                decode_entity(&caps[1]).map_or_else(|| caps[0].to_string(), String::from)
+            })
+            .into_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_body() {
+        let html =
+            "<html><head><title>Title</title></head><body class=\"main\"><p>本文</p></body></html>";
+        assert_eq!(plain_text(html), "本文");
+    }
+
+    #[test]
+    fn drops_furigana() {
+        let html = "<body><ruby>漢字<rp>(</rp><rt>かんじ</rt><rp>)</rp></ruby>です</body>";
+        assert_eq!(plain_text(html), "漢字です");
+    }
+
+    #[test]
+    fn drops_scripts_styles_and_comments() {
+        let html = "<body><style>p { color: red; }</style><!-- note --><p>文</p><script type=\"text/javascript\">let x = 1;</script></body>";
+        assert_eq!(plain_text(html), "文");
+    }
+
+    #[test]
+    fn normalizes_line_breaks() {
+        assert_eq!(plain_text("<body>一\r\n二\r三\n</body>"), "一\n二\n三\n");
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(
+            plain_text("<body>&lt;&amp;&gt;&#12354;&#x3042;&hellip;</body>"),
+            "<&>ああ…"
+        );
+        // Unknown entities are counted as written
+        assert_eq!(plain_text("<body>&unknown;</body>"), "&unknown;");
+    }
+}
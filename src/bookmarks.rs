@@ -0,0 +1,216 @@
+use dioxus::prelude::*;
+
+use crate::{
+    cfi::Cfi,
+    export,
+    read_state::ReaderState,
+    scan,
+    user_db::{self, Bookmark},
+};
+
+// Enough text to recognize the place by
+const SNIPPET_CHARS: usize = 40;
+
+/// Bookmarks the position at the top of the reader.
+async fn add_bookmark(read_state: &UseRef<Option<ReaderState>>, name: String, note: String) {
+    let Some((book, chapter, title, idref)) = read_state.with(|state| {
+        state.as_ref().map(|s| {
+            let chapter = s.get_page();
+            (
+                s.get_title(),
+                chapter,
+                s.get_chapter_title(chapter),
+                s.get_spine_id(chapter),
+            )
+        })
+    }) else {
+        return;
+    };
+
+    let Some(offset) = scan::first_visible_offset(scan::READER_CONTENT_ID, "reader-scroll") else {
+        log::warn!("Cannot find the position to bookmark");
+        return;
+    };
+    let cfi = idref.and_then(|idref| {
+        let position = scan::position_at(scan::READER_CONTENT_ID, offset)?;
+        Cfi::from_position(chapter, &idref, scan::READER_CONTENT_ID, &position)
+    });
+    let text = scan::root_text(scan::READER_CONTENT_ID)
+        .map(|text| {
+            text.chars()
+                .skip(offset)
+                .take(SNIPPET_CHARS)
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    let name = if name.trim().is_empty() {
+        title.unwrap_or_else(|| format!("Chapter {}", chapter + 1))
+    } else {
+        name.trim().to_string()
+    };
+
+    let bookmark = Bookmark {
+        id: None,
+        name,
+        note: note.trim().to_string(),
+        book,
+        chapter,
+        offset,
+        cfi: cfi.map(|cfi| cfi.to_string()),
+        text,
+        timestamp: js_sys::Date::now(),
+    };
+
+    if let Err(e) = user_db::add_bookmark(&bookmark).await {
+        log::error!("Cannot add bookmark due to error {e}");
+    }
+}
+
+#[derive(Props)]
+pub struct BookmarksProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+}
+
+pub fn bookmarks_component<'a>(cx: Scope<'a, BookmarksProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+
+    let name = use_state(cx, String::new);
+    let note = use_state(cx, String::new);
+    // Bumped to reload the list after adding or removing bookmarks
+    let revision = use_state(cx, || 0_u32);
+
+    let book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let bookmarks = use_future(cx, (&book, revision.get()), |(book, _)| async move {
+        match book {
+            Some(book) => user_db::get_bookmarks(&book).await,
+            None => Ok(Vec::new()),
+        }
+    });
+
+    if book.is_none() {
+        return cx.render(rsx!(p{ class: "mt-2", "No document" }));
+    }
+
+    let bookmarks = match bookmarks.value() {
+        None => return cx.render(rsx!(p{"Loading bookmarks"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load bookmarks: {e}"}));
+        }
+        Some(Ok(bookmarks)) => bookmarks,
+    };
+
+    cx.render(rsx! {
+        form{
+            class: "flex flex-col gap-2 mt-2",
+            prevent_default: "onsubmit",
+
+            onsubmit: move |_| {
+                let read_state = read_state.clone();
+                let revision = revision.clone();
+                let (new_name, new_note) = (name.get().clone(), note.get().clone());
+                name.set(String::new());
+                note.set(String::new());
+
+                wasm_bindgen_futures::spawn_local(async move {
+                    add_bookmark(&read_state, new_name, new_note).await;
+                    revision.modify(|r| r.wrapping_add(1));
+                });
+            },
+
+            input{
+                class: "bg-gray-100 rounded p-1",
+                placeholder: "Name, or the chapter title if empty",
+                value: "{name}",
+                oninput: move |evt: FormEvent| name.set(evt.value.clone()),
+            }
+
+            textarea{
+                class: "bg-gray-100 rounded p-1",
+                placeholder: "Note",
+                rows: "2",
+                value: "{note}",
+                oninput: move |evt: FormEvent| note.set(evt.value.clone()),
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+                r#type: "submit",
+
+                "Bookmark this position"
+            }
+        }
+
+        bookmarks.is_empty().then(|| rsx!(p{
+            class: "mt-2",
+
+            "No bookmarks in this book yet."
+        }))
+
+        ul{
+            class: "list-none",
+
+            bookmarks.iter().map(|bookmark| {
+                let id = bookmark.id.unwrap_or_default();
+                let (chapter, offset) = (bookmark.chapter, bookmark.offset);
+                let cfi = bookmark.cfi.as_deref().and_then(Cfi::parse);
+                let chapter_number = chapter + 1;
+                let date = export::format_timestamp(bookmark.timestamp);
+
+                rsx!(li{
+                    key: "{id}",
+                    class: "border-b py-1",
+
+                    h2{
+                        class: "text-lg font-medium inline-block mr-2",
+
+                        "{bookmark.name}"
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                        // The CFI puts the bookmarked text at the top again, like when it was added,
+                        // while the offset is saved with the chapter, so it is used if the CFI doesn't fit
+                        onclick: move |_| read_state.with_mut(|state| {
+                            if let Some(state) = state.as_mut() {
+                                if !cfi.clone().map_or(false, |cfi| state.jump_to_cfi(cfi)) {
+                                    state.jump_to(chapter, Some(offset));
+                                }
+                            }
+                        }),
+
+                        "Go to"
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                        onclick: move |_| {
+                            let revision = revision.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                if let Err(e) = user_db::delete_bookmark(id).await {
+                                    log::error!("Cannot remove bookmark due to error {e}");
+                                }
+                                revision.modify(|r| r.wrapping_add(1));
+                            });
+                        },
+
+                        "Remove"
+                    }
+
+                    (!bookmark.note.is_empty()).then(|| rsx!(p{ "{bookmark.note}" }))
+                    p{
+                        class: "text-sm text-gray-600",
+
+                        "Chapter {chapter_number}, {date}: {bookmark.text}"
+                    }
+                })
+            })
+        }
+    })
+}
@@ -0,0 +1,443 @@
+extern crate web_sys;
+
+use std::{fmt, iter::Peekable, str::Chars};
+
+use wasm_bindgen::JsCast;
+use web_sys::{Element, Node};
+
+use crate::scan::{self, TextPosition};
+
+// Step of the spine in the package document, after the metadata and the manifest
+const SPINE_STEP: usize = 6;
+// Step of the body in a chapter, after the head.
+// Only the body is rendered, so the reader's root stands in for it.
+const BODY_STEP: usize = 4;
+
+/// A step down the document tree. Even indices are elements, odd ones the text between them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Step {
+    index: usize,
+    /// Id of the element, which still finds it if the document changed a bit
+    id: Option<String>,
+}
+
+/// An EPUB Canonical Fragment Identifier, pointing to a position in a chapter.
+/// Only positions in text are supported, not ranges or spatial and temporal offsets.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cfi {
+    spine_step: usize,
+    /// Id of the chapter in the spine
+    idref: Option<String>,
+    steps: Vec<Step>,
+    /// Offset in UTF-16 code units within the text the last step points to
+    offset: Option<u32>,
+}
+
+fn escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, '^' | '[' | ']' | '(' | ')' | ',' | ';' | '=') {
+            escaped.push('^');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+fn number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+
+    digits.parse().ok()
+}
+
+/// Reads an assertion in brackets, like the id of an element.
+fn assertion(chars: &mut Peekable<Chars>) -> Option<String> {
+    chars.next_if_eq(&'[')?;
+
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => text.extend(chars.next()),
+            ']' => return Some(text),
+            _ => text.push(c),
+        }
+    }
+
+    None
+}
+
+/// Parses steps like `/4/2[intro]/3:10`.
+fn parse_path(path: &str) -> Option<(Vec<Step>, Option<u32>)> {
+    let mut steps = Vec::new();
+    let mut offset = None;
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' => {
+                let index = number(&mut chars)?;
+                let id = assertion(&mut chars).filter(|id| !id.is_empty());
+                steps.push(Step { index, id });
+            }
+            ':' => {
+                offset = u32::try_from(number(&mut chars)?).ok();
+                break;
+            }
+            // Ranges, spatial and temporal offsets aren't supported, so start of what they refer to
+            _ => break,
+        }
+    }
+
+    Some((steps, offset))
+}
+
+/// Length of the text of `node` in UTF-16 code units, like DOM offsets.
+fn text_length(node: &Node) -> u32 {
+    let length = node
+        .text_content()
+        .unwrap_or_default()
+        .encode_utf16()
+        .count();
+    u32::try_from(length).unwrap_or(u32::MAX)
+}
+
+/// Position of `node` among the element children of its parent, as a step index.
+fn element_step(node: &Node) -> Option<usize> {
+    let parent = node.parent_node()?;
+    let children = parent.child_nodes();
+
+    let mut elements = 0;
+    for i in 0..children.length() {
+        let child = children.item(i)?;
+        if child == *node {
+            return Some((elements + 1) * 2);
+        }
+        if child.node_type() == Node::ELEMENT_NODE {
+            elements += 1;
+        }
+    }
+
+    None
+}
+
+/// Step index of the text around `node`, and how many code units of that text come before it.
+fn text_step(node: &Node) -> Option<(usize, u32)> {
+    let parent = node.parent_node()?;
+    let children = parent.child_nodes();
+
+    let mut elements = 0;
+    let mut preceding = 0;
+    for i in 0..children.length() {
+        let child = children.item(i)?;
+        if child == *node {
+            return Some((elements * 2 + 1, preceding));
+        }
+
+        // Text is split into steps by elements, adjacent text nodes count as one
+        if child.node_type() == Node::ELEMENT_NODE {
+            elements += 1;
+            preceding = 0;
+        } else if child.node_type() == Node::TEXT_NODE {
+            preceding += text_length(&child);
+        }
+    }
+
+    None
+}
+
+/// The element child of `node` with the given step index.
+fn element_child(node: &Node, index: usize) -> Option<Node> {
+    let wanted = (index / 2).checked_sub(1)?;
+    let children = node.child_nodes();
+
+    (0..children.length())
+        .filter_map(|i| children.item(i))
+        .filter(|child| child.node_type() == Node::ELEMENT_NODE)
+        .nth(wanted)
+}
+
+/// The position `offset` code units into the text with the given step index in `node`.
+fn text_child(node: &Node, index: usize, offset: u32) -> Option<TextPosition> {
+    let elements_before = (index - 1) / 2;
+    let children = node.child_nodes();
+
+    let mut elements = 0;
+    let mut remaining = offset;
+    let mut last = None;
+    for child in (0..children.length()).filter_map(|i| children.item(i)) {
+        if child.node_type() == Node::ELEMENT_NODE {
+            elements += 1;
+            if elements > elements_before {
+                break;
+            }
+        } else if child.node_type() == Node::TEXT_NODE && elements == elements_before {
+            let length = text_length(&child);
+            if remaining <= length {
+                return Some(TextPosition {
+                    node: child,
+                    offset: remaining,
+                });
+            }
+
+            remaining -= length;
+            last = Some(TextPosition {
+                node: child,
+                offset: length,
+            });
+        }
+    }
+
+    // The text may have been shortened, so settle for its end
+    last
+}
+
+impl Cfi {
+    /// Points to the start of the chapter `page`, whose id in the spine is `idref`.
+    pub fn chapter_start(page: usize, idref: &str) -> Self {
+        Self {
+            spine_step: (page + 1) * 2,
+            idref: Some(idref.to_string()),
+            steps: vec![Step {
+                index: BODY_STEP,
+                id: None,
+            }],
+            offset: None,
+        }
+    }
+
+    /// Points to `position` in the chapter `page` rendered into the element `root_id`.
+    pub fn from_position(
+        page: usize,
+        idref: &str,
+        root_id: &str,
+        position: &TextPosition,
+    ) -> Option<Self> {
+        let root = scan::root_by_id(root_id)?;
+        if !root.contains(Some(&position.node)) {
+            return None;
+        }
+
+        let (index, preceding) = text_step(&position.node)?;
+        let mut steps = vec![Step { index, id: None }];
+
+        let mut node = position.node.parent_node()?;
+        while node != root {
+            let id = node
+                .dyn_ref::<Element>()
+                .map(Element::id)
+                .filter(|id| !id.is_empty());
+            steps.push(Step {
+                index: element_step(&node)?,
+                id,
+            });
+            node = node.parent_node()?;
+        }
+
+        steps.push(Step {
+            index: BODY_STEP,
+            id: None,
+        });
+        steps.reverse();
+
+        Some(Self {
+            steps,
+            offset: Some(preceding + position.offset),
+            ..Self::chapter_start(page, idref)
+        })
+    }
+
+    /// Points to the character `offset` in the reader, in the chapter `page`.
+    pub fn at_offset(page: usize, idref: &str, offset: usize) -> Option<Self> {
+        let position = scan::position_at(scan::READER_CONTENT_ID, offset)?;
+
+        Self::from_position(page, idref, scan::READER_CONTENT_ID, &position)
+    }
+
+    pub fn parse(cfi: &str) -> Option<Self> {
+        let inner = cfi.trim().strip_prefix("epubcfi(")?.strip_suffix(')')?;
+        let (package, content) = inner.split_once('!')?;
+
+        let (package, _) = parse_path(package)?;
+        let spine = package.get(1)?;
+        let (steps, offset) = parse_path(content)?;
+
+        // Only positions in chapters, not eg in the metadata, can be shown
+        if package.first()?.index != SPINE_STEP || steps.first()?.index != BODY_STEP {
+            return None;
+        }
+
+        Some(Self {
+            spine_step: spine.index,
+            idref: spine.id.clone(),
+            steps,
+            offset,
+        })
+    }
+
+    /// Index of the chapter in `spine`.
+    /// If the CFI names the chapter, it must be in the spine, since the CFI may be from another edition.
+    pub fn page(&self, spine: &[String]) -> Option<usize> {
+        if let Some(idref) = &self.idref {
+            return spine.iter().position(|id| id == idref);
+        }
+
+        let page = (self.spine_step / 2).checked_sub(1)?;
+        (page < spine.len()).then_some(page)
+    }
+
+    /// Finds the position in the chapter rendered into the element `root_id`.
+    pub fn resolve(&self, root_id: &str) -> Option<TextPosition> {
+        let window = web_sys::window().expect("should have window");
+        let document = window.document().expect("should have document");
+
+        let root = scan::root_by_id(root_id)?;
+        let offset = self.offset.unwrap_or_default();
+
+        // The body is the root itself
+        let Some((last, path)) = self.steps.get(1..)?.split_last() else {
+            return scan::first_text_at(&root, &root, 0);
+        };
+
+        let mut node = root.clone();
+        for step in path {
+            let by_id = step
+                .id
+                .as_ref()
+                .and_then(|id| document.get_element_by_id(id))
+                .map(Node::from)
+                .filter(|element| root.contains(Some(element)));
+
+            node = match by_id {
+                Some(element) => element,
+                None if step.index % 2 == 0 => element_child(&node, step.index)?,
+                None => return None,
+            };
+        }
+
+        if last.index % 2 == 0 {
+            let element = element_child(&node, last.index)?;
+            scan::first_text_at(&root, &element, 0)
+        } else {
+            text_child(&node, last.index, offset)
+        }
+    }
+
+    /// Character offset of the position in the element `root_id`, as counted by `scan`.
+    pub fn char_offset(&self, root_id: &str) -> Option<usize> {
+        scan::offset_of(root_id, &self.resolve(root_id)?)
+    }
+}
+
+impl fmt::Display for Cfi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "epubcfi(/{SPINE_STEP}/{}", self.spine_step)?;
+        if let Some(idref) = &self.idref {
+            write!(f, "[{}]", escape(idref))?;
+        }
+
+        write!(f, "!")?;
+        for step in &self.steps {
+            write!(f, "/{}", step.index)?;
+            if let Some(id) = &step.id {
+                write!(f, "[{}]", escape(id))?;
+            }
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, ":{offset}")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for cfi in [
+            "epubcfi(/6/4[chap01]!/4/2[intro]/3:10)",
+            "epubcfi(/6/2!/4)",
+            "epubcfi(/6/8[c^[1^]]!/4/6/1:0)",
+        ] {
+            assert_eq!(Cfi::parse(cfi).unwrap().to_string(), cfi);
+        }
+    }
+
+    #[test]
+    fn parses_parts() {
+        let cfi = Cfi::parse("epubcfi(/6/4[chap01]!/4/2[intro]/3:10)").unwrap();
+
+        assert_eq!(cfi.spine_step, 4);
+        assert_eq!(cfi.idref.as_deref(), Some("chap01"));
+        assert_eq!(cfi.offset, Some(10));
+        assert_eq!(
+            cfi.page(&["cover".to_string(), "chap01".to_string()]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn finds_chapters_by_id_first() {
+        let spine = ["cover".to_string(), "chap01".to_string()];
+
+        // The id wins over the position, and a missing id isn't made up for by it
+        let cfi = Cfi::parse("epubcfi(/6/2[chap01]!/4)").unwrap();
+        assert_eq!(cfi.page(&spine), Some(1));
+        let cfi = Cfi::parse("epubcfi(/6/4[chap02]!/4)").unwrap();
+        assert_eq!(cfi.page(&spine), None);
+
+        let cfi = Cfi::parse("epubcfi(/6/4!/4)").unwrap();
+        assert_eq!(cfi.page(&spine), Some(1));
+        let cfi = Cfi::parse("epubcfi(/6/6!/4)").unwrap();
+        assert_eq!(cfi.page(&spine), None);
+    }
+
+    #[test]
+    fn escapes_assertions() {
+        assert_eq!(escape("a[b]^c"), "a^[b^]^^c");
+
+        let cfi = Cfi::parse("epubcfi(/6/4[a^[b^]]!/4/2[x^]y]/1:0)").unwrap();
+        assert_eq!(cfi.idref.as_deref(), Some("a[b]"));
+        assert_eq!(cfi.steps[1].id.as_deref(), Some("x]y"));
+    }
+
+    #[test]
+    fn parses_path_offsets() {
+        let (steps, offset) = parse_path("/4/2[intro]/3:10").unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step { index: 4, id: None },
+                Step {
+                    index: 2,
+                    id: Some("intro".to_string())
+                },
+                Step { index: 3, id: None },
+            ]
+        );
+        assert_eq!(offset, Some(10));
+
+        // Ranges and spatial offsets end the path
+        let (steps, offset) = parse_path("/4/1@20:30").unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(offset, None);
+
+        assert!(parse_path("/x").is_none());
+    }
+
+    #[test]
+    fn rejects_other_paths() {
+        // Not in the spine
+        assert!(Cfi::parse("epubcfi(/4/2!/4/1:0)").is_none());
+        // Not in the body
+        assert!(Cfi::parse("epubcfi(/6/2!/2/1:0)").is_none());
+        // Not a CFI at all
+        assert!(Cfi::parse("/6/2!/4").is_none());
+        assert!(Cfi::parse("epubcfi(/6/2)").is_none());
+    }
+}
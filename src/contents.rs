@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{analysis::AnalysisState, read_state::ReaderState};
+
+#[derive(Props)]
+pub struct ContentsProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+    analysis: &'a UseRef<AnalysisState>,
+    known: &'a UseRef<HashSet<String>>,
+}
+
+/// The chapters of the open book, with their difficulty once the book is analysed.
+pub fn contents_component<'a>(cx: Scope<'a, ContentsProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+
+    let Some((book, current_page, chapters)) = read_state.with(|state| {
+        let state = state.as_ref()?;
+        let chapters = (0..state.get_page_count())
+            .map(|page| {
+                state
+                    .get_chapter_title(page)
+                    .unwrap_or_else(|| format!("Chapter {}", page + 1))
+            })
+            .collect::<Vec<_>>();
+        Some((state.get_title(), state.get_page(), chapters))
+    }) else {
+        return cx.render(rsx!(p{ class: "mt-2", "No document" }));
+    };
+
+    let analysis = cx.props.analysis.read();
+    let known = cx.props.known.read();
+    let result = match &*analysis {
+        AnalysisState::Done(result) if result.book == book => Some(result),
+        _ => None,
+    };
+
+    let book_info = result.map_or_else(
+        || {
+            "Analyze the book in the vocabulary panel to estimate how hard its chapters are."
+                .to_string()
+        },
+        |r| format!("Whole book: {}", r.book_difficulty(&known).summary()),
+    );
+
+    cx.render(rsx! {
+        p{
+            class: "text-sm text-gray-600 mt-2",
+
+            "{book_info}"
+        }
+
+        ol{
+            class: "list-none",
+
+            chapters.into_iter().enumerate().map(|(page, title)| {
+                let class = if page == current_page { "font-medium" } else { "" };
+                let difficulty = result
+                    .and_then(|r| r.chapter_difficulty(page, &known))
+                    .map(|d| d.summary());
+
+                rsx!(li{
+                    key: "{page}",
+                    class: "border-b py-1",
+
+                    button{
+                        class: "text-left hover:underline {class}",
+
+                        onclick: move |_| read_state.with_mut(|state| {
+                            if let Some(state) = state.as_mut() {
+                                state.jump_to(page, Some(0));
+                            }
+                        }),
+
+                        "{title}"
+                    }
+
+                    difficulty.map(|d| rsx!(p{
+                        class: "text-sm text-gray-600",
+
+                        "{d}"
+                    }))
+                })
+            })
+        }
+    })
+}
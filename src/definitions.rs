@@ -1,10 +1,23 @@
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
 use dioxus::prelude::*;
 use yomi_dict::DB;
 
+use crate::{
+    deinflection, highlight, known_words,
+    normalize::{self, Normalization},
+    scan::{self, Scan},
+};
+
 // This shouldn't be an issue since we only mutate the db on creation with load_db
 // https://github.com/rust-lang/rust-clippy/issues/6671
 #[allow(clippy::await_holding_refcell_ref)]
-async fn get_terms(
+pub(crate) async fn get_terms(
     text: &str,
     reasons: &yomi_dict::Reasons,
     db: &UseRef<Option<yomi_dict::IndexedDB>>,
@@ -14,64 +27,391 @@ async fn get_terms(
     db.find_terms(text, reasons).await
 }
 
-pub async fn update_defs_and_selection(
-    defs: &UseState<Vec<yomi_dict::DictEntries>>,
+fn match_len(d: &yomi_dict::DictEntries) -> usize {
+    d.entries
+        .iter()
+        .map(|e| e.source_len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Looks up every normalized variant of `text` and merges the results,
+/// with match lengths referring to `text` itself.
+pub(crate) async fn get_normalized_terms(
+    text: &str,
+    normalization: Normalization,
+    reasons: &yomi_dict::Reasons,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+) -> Result<Vec<yomi_dict::DictEntries>, yomi_dict::YomiDictError> {
+    let mut merged: Vec<yomi_dict::DictEntries> = Vec::new();
+    let text_len = text.chars().count();
+
+    // Variants are unique, so each is only looked up once
+    for variant in normalize::variants(text, normalization) {
+        // No variant can match more than all of the text
+        if merged.iter().any(|d| match_len(d) >= text_len) {
+            break;
+        }
+
+        for mut found in get_terms(&variant.text, reasons, db).await? {
+            for entry in &mut found.entries {
+                entry.source_len = variant.source_len(entry.source_len);
+            }
+
+            let existing = merged
+                .iter()
+                .position(|d| d.expression == found.expression && d.reading == found.reading);
+            match existing {
+                Some(i) if match_len(&merged[i]) < match_len(&found) => merged[i] = found,
+                Some(_) => {}
+                None => merged.push(found),
+            }
+        }
+    }
+
+    // Longer matches come first, like for a single lookup
+    merged.sort_by_key(|d| std::cmp::Reverse(match_len(d)));
+
+    Ok(merged)
+}
+
+// Recently looked up text, so looking it up again is instant
+const CACHE_SIZE: usize = 64;
+
+type CacheKey = (String, Normalization);
+
+thread_local! {
+    static CACHE: RefCell<VecDeque<(CacheKey, Rc<Vec<yomi_dict::DictEntries>>)>> =
+        RefCell::new(VecDeque::with_capacity(CACHE_SIZE));
+}
+
+// Lookups may finish out of order, so only the latest one may show its results
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Marks the start of a lookup, making all earlier ones stale.
+pub(crate) fn start_lookup() -> u32 {
+    GENERATION.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+}
+
+/// Whether no lookup was started after the one with `generation`.
+pub(crate) fn is_current(generation: u32) -> bool {
+    GENERATION.load(Ordering::Relaxed) == generation
+}
+
+/// Forgets cached lookups, eg because a dictionary was added.
+pub(crate) fn clear_cache() {
+    CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Like `get_normalized_terms`, but remembers the most recent results.
+pub(crate) async fn get_cached_terms(
+    text: &str,
+    normalization: Normalization,
+    reasons: &yomi_dict::Reasons,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+) -> Result<Rc<Vec<yomi_dict::DictEntries>>, yomi_dict::YomiDictError> {
+    let key = (text.to_string(), normalization);
+
+    // Most recently used entries are kept at the front
+    let cached = CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let index = cache.iter().position(|(k, _)| *k == key)?;
+        let entry = cache.remove(index)?;
+        let entries = entry.1.clone();
+        cache.push_front(entry);
+        Some(entries)
+    });
+    if let Some(entries) = cached {
+        return Ok(entries);
+    }
+
+    let entries = Rc::new(get_normalized_terms(text, normalization, reasons, db).await?);
+
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache.truncate(CACHE_SIZE - 1);
+        cache.push_front((key, entries.clone()));
+    });
+
+    Ok(entries)
+}
+
+/// Id of the element definitions are rendered into, so their glossaries can be scanned.
+pub const DEFINITIONS_CONTENT_ID: &str = "definitions-content";
+
+/// The result of looking up some scanned text.
+pub struct Lookup {
+    pub text: String,
+    pub entries: Rc<Vec<yomi_dict::DictEntries>>,
+}
+
+impl Lookup {
+    fn label(&self) -> &str {
+        self.entries
+            .first()
+            .map_or(self.text.as_str(), |d| d.expression.as_str())
+    }
+}
+
+/// What looking up scanned text in the book found.
+pub enum LookupResult {
+    /// A newer lookup started meanwhile, so this one changed nothing
+    Stale,
+    /// The match, which is highlighted
+    Found(web_sys::Range),
+    NotFound,
+}
+
+/// Looks up the scanned text as the latest lookup, or returns None if a newer one started meanwhile.
+/// Failing lookups are logged and find nothing.
+async fn lookup_current(
     db: &UseRef<Option<yomi_dict::IndexedDB>>,
     reasons: &yomi_dict::Reasons,
-    data: &str,
-) {
+    normalization: Normalization,
+    scan: &Scan,
+) -> Option<Rc<Vec<yomi_dict::DictEntries>>> {
     if db.read().is_none() {
-        // TODO reset selection
         log::error!("Cannot update definitions since DB is not loaded yet!");
-        return;
+        return Some(Rc::default());
     }
 
-    let entries = match get_terms(data, reasons, db).await {
+    let generation = start_lookup();
+    let entries = match get_cached_terms(&scan.text, normalization, reasons, db).await {
         Ok(entries) => entries,
         Err(e) => {
             log::error!("Cannot get definitions due to error {}", e);
-            return;
+            Rc::default()
         }
     };
 
-    let window = web_sys::window().expect("should have window");
-    let selection = window.get_selection().expect("Should have selection");
-    if let Some(selection) = selection {
-        // TODO ensure we're only modifying our own selection
-        selection.collapse_to_start().unwrap();
+    if !is_current(generation) {
+        log::info!("Discarding stale lookup of {}", scan.text);
+        return None;
+    }
+
+    Some(entries)
+}
 
-        let len = entries
-            .first()
-            .map_or(0, |entry| entry.entries[0].source_len);
-
-        // Can't just execute `len` times because line-breaks etc. count extra.
-        // Note that this is inefficient and a lot of work like `chars()` is repeated.
-        // It's simple though and seems fast enough.
-        while selection
-            .to_string()
-            .as_string()
-            .map_or(false, |s| s.chars().count() != len)
-        {
-            selection.modify("extend", "forward", "character").unwrap();
-        }
+/// Looks up the scanned text and highlights the match, which is returned.
+/// This replaces all earlier lookups.
+pub async fn update_defs_and_highlight(
+    defs: &UseRef<Vec<Lookup>>,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+    normalization: Normalization,
+    scan: &Scan,
+) -> LookupResult {
+    let Some(entries) = lookup_current(db, reasons, normalization, scan).await else {
+        return LookupResult::Stale;
+    };
+
+    let len = entries
+        .first()
+        .map_or(0, |entry| entry.entries[0].source_len);
+
+    let range = scan.range(len).filter(|_| len > 0);
+    match &range {
+        Some(range) => highlight::set(highlight::MATCH, &[range.clone()]),
+        None => highlight::clear(highlight::MATCH),
+    }
+
+    defs.set(vec![Lookup {
+        text: scan.text.clone(),
+        entries,
+    }]);
+
+    range.map_or(LookupResult::NotFound, LookupResult::Found)
+}
+
+/// Looks up text scanned within the definitions, which is shown on top of the earlier lookups.
+/// Returns whether anything was found.
+pub async fn push_lookup(
+    defs: &UseRef<Vec<Lookup>>,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+    normalization: Normalization,
+    scan: &Scan,
+) -> bool {
+    let Some(entries) = lookup_current(db, reasons, normalization, scan).await else {
+        return false;
+    };
+
+    if entries.is_empty() {
+        return false;
+    }
+
+    defs.with_mut(|defs| {
+        defs.push(Lookup {
+            text: scan.text.clone(),
+            entries,
+        });
+    });
+
+    true
+}
+
+fn clicked(onlookup: &EventHandler<Scan>) {
+    const SELECTION_LENGTH: usize = 16;
+
+    if let Some(scan) = scan::scan_selection(DEFINITIONS_CONTENT_ID, SELECTION_LENGTH) {
+        log::info!("Clicked in definitions: {}", scan.text);
+
+        onlookup.call(scan);
+    }
+}
+
+fn source_text(text: &str, source_len: usize) -> String {
+    text.chars().take(source_len).collect()
+}
+
+/// Explains how the looked up text was deinflected to the dictionary form of `d`,
+/// and which other deinflections of the same text were found.
+fn inflection<'a>(
+    text: &'a str,
+    d: &'a yomi_dict::DictEntries,
+    definitions: &'a [yomi_dict::DictEntries],
+) -> Option<LazyNodes<'a, 'a>> {
+    let first = &d.entries[0];
+    let source = source_text(text, first.source_len);
+
+    // Other ways the same text can be read, which were ranked lower
+    let mut alternatives = d
+        .entries
+        .iter()
+        .skip(1)
+        .filter(|e| e.reasons != first.reasons)
+        .map(|e| deinflection::chain(&source_text(text, e.source_len), &e.reasons, &d.expression))
+        .chain(
+            definitions
+                .iter()
+                .filter(|other| other.expression != d.expression)
+                .flat_map(|other| {
+                    other
+                        .entries
+                        .iter()
+                        .filter(|e| e.source_len == first.source_len && !e.reasons.is_empty())
+                        .map(|e| deinflection::chain(&source, &e.reasons, &other.expression))
+                }),
+        )
+        .collect::<Vec<_>>();
+    // Keep the order they were ranked in
+    let mut seen = HashSet::new();
+    alternatives.retain(|a| seen.insert(a.clone()));
+
+    if first.reasons.is_empty() && alternatives.is_empty() {
+        return None;
     }
 
-    defs.set(entries);
+    let notes = first
+        .reasons
+        .iter()
+        .filter_map(|r| deinflection::note(r).map(|note| (r, note)))
+        .collect::<Vec<_>>();
+
+    Some(rsx!(div{
+        class: "text-sm",
+
+        (!first.reasons.is_empty()).then(|| rsx!(p{
+            span{ class: "font-medium", "{source}" }
+            first.reasons.iter().map(|r| {
+                let note = deinflection::note(r).unwrap_or_default();
+                rsx!(
+                    " ← "
+                    span{
+                        class: "inline-block rounded-full m-1 px-2 bg-gray-300",
+                        title: "{note}",
+
+                        "{r}"
+                    }
+                )
+            })
+            " ← "
+            span{ class: "font-medium", "{d.expression}" }
+        }))
+
+        (!notes.is_empty()).then(|| rsx!(ul{
+            class: "list-none text-gray-600",
+
+            // The same rule can apply more than once in a chain
+            notes.into_iter().enumerate().map(|(i, (r, note))| rsx!(li{ key: "{i}", "{r}: {note}" }))
+        }))
+
+        (!alternatives.is_empty()).then(|| rsx!(details{
+            class: "text-gray-600",
+
+            summary{ "Also considered" }
+            ul{
+                class: "list-none px-4",
+
+                alternatives.into_iter().map(|a| rsx!(li{ key: "{a}", "{a}" }))
+            }
+        }))
+    }))
 }
 
 #[inline_props]
 pub fn definitions_component<'a>(
     cx: Scope,
-    definitions: &'a Vec<yomi_dict::DictEntries>,
+    lookups: &'a UseRef<Vec<Lookup>>,
+    known: &'a UseRef<HashSet<String>>,
+    onlookup: EventHandler<'a, Scan>,
 ) -> Element {
+    let stack = lookups.read();
+    let definitions = stack
+        .last()
+        .map_or(&[][..], |lookup| lookup.entries.as_slice());
+    let text = stack.last().map_or("", |lookup| lookup.text.as_str());
+
+    // Lookups from within definitions can be retraced
+    let breadcrumbs = (stack.len() > 1).then(|| {
+        rsx!(nav{
+            class: "flex flex-wrap items-center gap-1 text-sm",
+
+            button{
+                class: "rounded-full px-2 bg-gray-200 hover:bg-gray-300",
+
+                onclick: move |_| lookups.with_mut(|l| {
+                    l.pop();
+                }),
+
+                "Back"
+            }
+
+            stack.iter().enumerate().map(|(i, lookup)| {
+                let label = lookup.label();
+                let current = i + 1 == stack.len();
+                let class = if current { "font-medium" } else { "underline text-gray-600" };
+
+                rsx!(span{
+                    key: "{i}",
+
+                    (i > 0).then(|| rsx!("› "))
+                    button{
+                        class: "{class}",
+                        disabled: "{current}",
+
+                        onclick: move |_| lookups.with_mut(|l| l.truncate(i + 1)),
+
+                        "{label}"
+                    }
+                })
+            })
+        })
+    });
+
     let content = if definitions.is_empty() {
         rsx!(p{"Click the first letter of an expression to look it up!"})
     } else {
         rsx!(ul{
             class: "list-none",
 
-            definitions.iter().map(|d| rsx!(
-                li{
+            definitions.iter().map(|d| {
+                let inflection = inflection(text, d, definitions);
+                let is_known = known.read().contains(&d.expression);
+                let known_label = if is_known { "Known ✓" } else { "Mark known" };
+                let (expression, reading) = (d.expression.clone(), d.reading.clone());
+
+                rsx!(li{
                     key: "{d.expression}/{d.reading}",
                     h2{
                         class: "text-xl font-medium inline-block",
@@ -81,16 +421,23 @@ pub fn definitions_component<'a>(
                             rt{ "{d.reading}" }
                         }
                     }
-                    span{
-                        d.entries[0].reasons.iter().map(|r|
-                            rsx!(
-                                p{
-                                    class: "inline-block text-sm rounded-full m-1 p-1 px-2 bg-gray-300",
-                                    "{r}"
-                                }
-                            )
-                        )
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                        // Clicking here shouldn't scan the definitions
+                        onclick: move |evt| {
+                            evt.stop_propagation();
+
+                            let known = known.clone();
+                            let (expression, reading) = (expression.clone(), reading.clone());
+                            wasm_bindgen_futures::spawn_local(async move {
+                                known_words::set_known(&known, expression, reading, !is_known).await;
+                            });
+                        },
+
+                        "{known_label}"
                     }
+                    inflection
                     div{
                         ol{
                             class: "list-decimal px-4",
@@ -119,14 +466,21 @@ pub fn definitions_component<'a>(
                             })
                         }
                     }
-                }
-            ))
+                })
+            })
         })
     };
 
     cx.render(rsx!(aside {
         class: "container mx-auto mt-2",
 
-        content
+        breadcrumbs
+
+        div{
+            id: DEFINITIONS_CONTENT_ID,
+            onclick: |_| clicked(onlookup),
+
+            content
+        }
     }))
 }
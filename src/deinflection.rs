@@ -0,0 +1,101 @@
+/// Short explanations of the inflection reasons Yomichan dictionaries use.
+const NOTES: &[(&str, &str)] = &[
+    ("-ba", "Conditional: if ..."),
+    ("-chau", "Casual contraction of -te shimau"),
+    ("-chimau", "Casual contraction of -te shimau"),
+    ("-shimau", "Doing something completely, or regrettably"),
+    ("-nasai", "Polite command"),
+    ("-sou", "Seeming, looking like ..."),
+    ("-sugiru", "Too much, excessively"),
+    ("-tai", "Wanting to do ..."),
+    ("-tara", "Conditional: if or when ..."),
+    ("-tari", "Listing example actions"),
+    ("-te", "Connective form, joins clauses or auxiliaries"),
+    ("-zu", "Literary negative: without doing ..."),
+    ("-nu", "Literary negative"),
+    ("-n", "Colloquial negative"),
+    ("-toku", "Casual contraction of -te oku: doing in advance"),
+    ("-te iru", "Ongoing action or resulting state"),
+    ("-e", "Literary imperative"),
+    ("-ki", "Literary attributive form of adjectives"),
+    ("-ge", "Seeming, showing signs of ..."),
+    ("-garu", "Showing signs of a feeling"),
+    ("-ya", "Casual contraction of -eba"),
+    ("-nagara", "While doing ..."),
+    ("adv", "Adverbial form of an adjective"),
+    ("causative", "Making or letting someone do ..."),
+    ("causative passive", "Being made to do ..."),
+    ("imperative", "Command"),
+    ("imperative negative", "Prohibition: don't ..."),
+    ("masu", "Polite form"),
+    ("negative", "Not doing ..."),
+    ("noun", "Noun made from an adjective"),
+    ("passive", "Having something done to one"),
+    ("past", "Completed action or past state"),
+    ("polite", "Polite form"),
+    ("polite negative", "Polite form of not doing ..."),
+    ("polite past", "Polite form of the past"),
+    ("polite past negative", "Polite form of not having done ..."),
+    ("polite volitional", "Polite form of let's ..."),
+    ("potential", "Being able to do ..."),
+    (
+        "potential or passive",
+        "Being able to do, or having something done to one",
+    ),
+    (
+        "progressive or perfect",
+        "Ongoing action or resulting state",
+    ),
+    ("volitional", "Let's ..., or intending to ..."),
+    ("stem-ku", "Adverbial stem of an adjective"),
+];
+
+/// Explains what the inflection `reason` means, if we know it.
+pub fn note(reason: &str) -> Option<&'static str> {
+    NOTES
+        .iter()
+        .find(|(r, _)| *r == reason)
+        .map(|(_, note)| *note)
+}
+
+/// Describes how `source` relates to its dictionary form `expression`,
+/// eg 食べなかった ← negative ← past ← 食べる.
+pub fn chain(source: &str, reasons: &[String], expression: &str) -> String {
+    std::iter::once(source)
+        .chain(reasons.iter().map(String::as_str))
+        .chain(std::iter::once(expression))
+        .collect::<Vec<_>>()
+        .join(" ← ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explains_known_reasons() {
+        assert_eq!(note("masu"), Some("Polite form"));
+        assert_eq!(note("causative passive"), Some("Being made to do ..."));
+        assert_eq!(note("unknown"), None);
+    }
+
+    #[test]
+    fn has_one_note_per_reason() {
+        for (i, (reason, _)) in NOTES.iter().enumerate() {
+            assert!(
+                NOTES[i + 1..].iter().all(|(other, _)| other != reason),
+                "{reason} is explained twice"
+            );
+        }
+    }
+
+    #[test]
+    fn chains_reasons() {
+        let reasons = ["negative", "past"].map(String::from);
+        assert_eq!(
+            chain("食べなかった", &reasons, "食べる"),
+            "食べなかった ← negative ← past ← 食べる"
+        );
+        assert_eq!(chain("食べる", &[], "食べる"), "食べる ← 食べる");
+    }
+}
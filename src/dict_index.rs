@@ -0,0 +1,612 @@
+extern crate web_sys;
+
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use rexie::{Index, KeyRange, ObjectStore, Rexie, TransactionMode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+// The dictionary database itself can only be queried with `find_terms`,
+// so we keep our own indices for other kinds of searches next to it.
+
+const TERMS: &str = "terms";
+const GLOSSARY: &str = "glossary";
+const FREQUENCIES: &str = "frequencies";
+const DICTIONARIES: &str = "dictionaries";
+
+// Raised whenever the indices change shape, so older ones get rebuilt
+const INDEX_VERSION: u32 = 3;
+
+// Words too common in glossaries to be useful for searching
+const STOP_WORDS: [&str; 12] = [
+    "a", "an", "the", "to", "of", "in", "on", "at", "for", "or", "and", "be",
+];
+
+/// Expression and reading of a dictionary term,
+/// reversed as well so they can be searched by suffix.
+#[derive(Serialize, Deserialize)]
+struct IndexedTerm {
+    expression: String,
+    reading: String,
+    expression_reversed: String,
+    reading_reversed: String,
+}
+
+impl IndexedTerm {
+    fn new(expression: String, reading: String) -> Self {
+        // Like in Yomichan, an empty reading means the expression is written in kana
+        let reading = if reading.is_empty() {
+            expression.clone()
+        } else {
+            reading
+        };
+
+        Self {
+            expression_reversed: expression.chars().rev().collect(),
+            reading_reversed: reading.chars().rev().collect(),
+            expression,
+            reading,
+        }
+    }
+}
+
+/// A word appearing in the glossary of a term, for reverse searches.
+#[derive(Serialize, Deserialize)]
+struct GlossaryWord {
+    word: String,
+    expression: String,
+    reading: String,
+    gloss: String,
+    score: i64,
+}
+
+/// A dictionary whose terms are in the indices.
+#[derive(Serialize, Deserialize)]
+struct IndexedDict {
+    title: String,
+    revision: String,
+    version: u32,
+}
+
+/// Whether the indices cover the dictionaries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexStatus {
+    Current,
+    /// No dictionary was indexed yet, eg because they were imported before indexing existed
+    Empty,
+    /// Some dictionary was indexed by an older version
+    Outdated,
+}
+
+/// Frequency rank of an expression, where 1 is the most frequent.
+#[derive(Serialize, Deserialize)]
+struct Frequency {
+    expression: String,
+    rank: u64,
+}
+
+async fn get_index_db() -> rexie::Result<Rexie> {
+    Rexie::builder("DictIndexDatabase")
+        .version(INDEX_VERSION)
+        .add_object_store(
+            ObjectStore::new(TERMS)
+                .auto_increment(true)
+                .add_index(Index::new("expression", "expression"))
+                .add_index(Index::new("reading", "reading"))
+                .add_index(Index::new("expression_reversed", "expression_reversed"))
+                .add_index(Index::new("reading_reversed", "reading_reversed")),
+        )
+        .add_object_store(
+            ObjectStore::new(GLOSSARY)
+                .auto_increment(true)
+                .add_index(Index::new("word", "word")),
+        )
+        .add_object_store(ObjectStore::new(FREQUENCIES).key_path("expression"))
+        .add_object_store(ObjectStore::new(DICTIONARIES).key_path("title"))
+        .build()
+        .await
+}
+
+fn term_field(term: &[serde_json::Value], index: usize) -> String {
+    term.get(index)
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn json_field(object: &serde_json::Value, key: &str) -> String {
+    object
+        .get(key)
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Collects the text of a glossary entry, which is either a string or structured content.
+fn gloss_texts(gloss: &serde_json::Value, texts: &mut Vec<String>) {
+    match gloss {
+        serde_json::Value::String(text) => texts.push(text.clone()),
+        serde_json::Value::Array(content) => {
+            for c in content {
+                gloss_texts(c, texts);
+            }
+        }
+        serde_json::Value::Object(object) => {
+            if let Some(text) = object.get("text") {
+                gloss_texts(text, texts);
+            }
+            if let Some(content) = object.get("content") {
+                gloss_texts(content, texts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Splits English text into lower case words, leaving out stop words.
+fn words(text: &str) -> Vec<String> {
+    let mut words = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(str::to_lowercase)
+        .filter(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()))
+        .collect::<Vec<_>>();
+    words.sort_unstable();
+    words.dedup();
+
+    words
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(
+    archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<T, IndexError> {
+    let mut content = String::new();
+    archive.by_name(name)?.read_to_string(&mut content)?;
+
+    Ok(serde_json::from_str(&content)?)
+}
+
+async fn index_terms(db: &Rexie, bank: Vec<Vec<serde_json::Value>>) -> Result<(), IndexError> {
+    let transaction = db.transaction(&[TERMS, GLOSSARY], TransactionMode::ReadWrite)?;
+    let terms = transaction.store(TERMS)?;
+    let glossary = transaction.store(GLOSSARY)?;
+
+    // Rows are keyed by their content, so importing a dictionary again replaces them
+    let mut term_values = Vec::with_capacity(bank.len());
+    let mut word_values = Vec::new();
+
+    // Each term is a JSON array of expression, reading, tags, rules, score and glossary
+    for term in bank {
+        let indexed = IndexedTerm::new(term_field(&term, 0), term_field(&term, 1));
+        let key = format!("{}\u{0}{}", indexed.expression, indexed.reading);
+        term_values.push((JsValue::from(key), serde_wasm_bindgen::to_value(&indexed)?));
+
+        let score = term
+            .get(4)
+            .and_then(serde_json::Value::as_i64)
+            .unwrap_or_default();
+
+        let mut glosses = Vec::new();
+        if let Some(gloss) = term.get(5) {
+            gloss_texts(gloss, &mut glosses);
+        }
+
+        for gloss in glosses {
+            for word in words(&gloss) {
+                let key = format!(
+                    "{word}\u{0}{}\u{0}{}\u{0}{gloss}",
+                    indexed.expression, indexed.reading
+                );
+                let word = GlossaryWord {
+                    word,
+                    expression: indexed.expression.clone(),
+                    reading: indexed.reading.clone(),
+                    gloss: gloss.clone(),
+                    score,
+                };
+                word_values.push((JsValue::from(key), serde_wasm_bindgen::to_value(&word)?));
+            }
+        }
+    }
+
+    // Waiting for every request on its own is slow, so they are all sent at once
+    let puts = term_values
+        .iter()
+        .map(|(key, value)| terms.put(value, Some(key)))
+        .chain(
+            word_values
+                .iter()
+                .map(|(key, value)| glossary.put(value, Some(key))),
+        );
+    futures::future::try_join_all(puts).await?;
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+/// Reads the frequency of a term meta entry, which may be given in a few different shapes.
+fn meta_frequency(data: &serde_json::Value) -> Option<f64> {
+    match data {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        serde_json::Value::Object(object) => object
+            .get("frequency")
+            .or_else(|| object.get("value"))
+            .and_then(meta_frequency),
+        _ => None,
+    }
+}
+
+async fn index_frequencies(
+    db: &Rexie,
+    frequencies: Vec<(String, f64)>,
+    occurrence_based: bool,
+) -> Result<(), IndexError> {
+    let mut frequencies = frequencies;
+
+    // Occurrence counts are turned into ranks, so dictionaries can be compared
+    if occurrence_based {
+        frequencies.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    }
+
+    let transaction = db.transaction(&[FREQUENCIES], TransactionMode::ReadWrite)?;
+    let store = transaction.store(FREQUENCIES)?;
+
+    for (i, (expression, value)) in frequencies.into_iter().enumerate() {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let rank = if occurrence_based {
+            u64::try_from(i + 1).unwrap_or(u64::MAX)
+        } else {
+            value.max(1.0) as u64
+        };
+
+        // Keep the best rank, ie the lowest number, any dictionary gives
+        let existing = store.get(&JsValue::from(expression.as_str())).await?;
+        if !existing.is_undefined() {
+            let existing: Frequency = serde_wasm_bindgen::from_value(existing)?;
+            if existing.rank <= rank {
+                continue;
+            }
+        }
+
+        let frequency = Frequency { expression, rank };
+        store
+            .put(&serde_wasm_bindgen::to_value(&frequency)?, None)
+            .await?;
+    }
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+/// Whether the indices are missing or were built by an older version.
+pub async fn index_status() -> Result<IndexStatus, IndexError> {
+    let db = get_index_db().await?;
+    let transaction = db.transaction(&[DICTIONARIES], TransactionMode::ReadOnly)?;
+    let dictionaries = transaction
+        .store(DICTIONARIES)?
+        .get_all(None, None, None, None)
+        .await?;
+
+    if dictionaries.is_empty() {
+        return Ok(IndexStatus::Empty);
+    }
+
+    for (_, value) in dictionaries {
+        let dictionary: IndexedDict = serde_wasm_bindgen::from_value(value)?;
+        if dictionary.version < INDEX_VERSION {
+            return Ok(IndexStatus::Outdated);
+        }
+    }
+
+    Ok(IndexStatus::Current)
+}
+
+/// Removes everything from the indices, so they can be rebuilt.
+async fn clear_indices(db: &Rexie) -> Result<(), IndexError> {
+    let stores = [TERMS, GLOSSARY, FREQUENCIES, DICTIONARIES];
+    let transaction = db.transaction(&stores, TransactionMode::ReadWrite)?;
+    for store in stores {
+        transaction.store(store)?.clear().await?;
+    }
+    transaction.done().await?;
+
+    Ok(())
+}
+
+/// Adds a dictionary in the Yomichan format to our indices,
+/// calling `progress` after every bank.
+///
+/// Indices that aren't current are rebuilt from scratch,
+/// as their rows can't be told apart from the new ones.
+pub async fn index_dict(
+    data: &[u8],
+    mut progress: impl FnMut(usize, usize),
+) -> Result<(), IndexError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+    let banks = archive
+        .file_names()
+        .filter(|name| {
+            (name.starts_with("term_bank_") || name.starts_with("term_meta_bank_"))
+                && name.ends_with(".json")
+        })
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    let index: serde_json::Value = read_json(&mut archive, "index.json")?;
+    let occurrence_based = index
+        .get("frequencyMode")
+        .and_then(serde_json::Value::as_str)
+        == Some("occurrence-based");
+    let dictionary = IndexedDict {
+        title: json_field(&index, "title"),
+        revision: json_field(&index, "revision"),
+        version: INDEX_VERSION,
+    };
+
+    if index_status().await? != IndexStatus::Current {
+        log::info!("Rebuilding dictionary indices");
+        clear_indices(&get_index_db().await?).await?;
+    }
+
+    let db = get_index_db().await?;
+
+    let mut frequencies = Vec::new();
+    for (i, bank) in banks.iter().enumerate() {
+        progress(i, banks.len());
+
+        let entries: Vec<Vec<serde_json::Value>> = read_json(&mut archive, bank)?;
+
+        if bank.starts_with("term_bank_") {
+            index_terms(&db, entries).await?;
+            continue;
+        }
+
+        // Term meta entries are arrays of expression, mode and data
+        frequencies.extend(entries.into_iter().filter_map(|entry| {
+            if entry.get(1)?.as_str()? != "freq" {
+                return None;
+            }
+            Some((term_field(&entry, 0), meta_frequency(entry.get(2)?)?))
+        }));
+    }
+
+    if !frequencies.is_empty() {
+        index_frequencies(&db, frequencies, occurrence_based).await?;
+    }
+
+    let transaction = db.transaction(&[DICTIONARIES], TransactionMode::ReadWrite)?;
+    transaction
+        .store(DICTIONARIES)?
+        .put(&serde_wasm_bindgen::to_value(&dictionary)?, None)
+        .await?;
+    transaction.done().await?;
+
+    progress(banks.len(), banks.len());
+
+    Ok(())
+}
+
+/// Finds up to `limit` expressions whose expression or reading matches `pattern`.
+///
+/// A leading `*` searches by suffix, eg `*語`, otherwise the pattern is searched as a prefix, eg `食べ*`.
+pub async fn find_wildcard(pattern: &str, limit: usize) -> Result<Vec<String>, IndexError> {
+    let (indices, key) = pattern.strip_prefix('*').map_or_else(
+        || {
+            let prefix = pattern.split('*').next().unwrap_or_default();
+            (["expression", "reading"], prefix.to_string())
+        },
+        |suffix| {
+            let suffix = suffix.replace('*', "");
+            (
+                ["expression_reversed", "reading_reversed"],
+                suffix.chars().rev().collect(),
+            )
+        },
+    );
+
+    if key.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let db = get_index_db().await?;
+    let transaction = db.transaction(&[TERMS], TransactionMode::ReadOnly)?;
+    let terms = transaction.store(TERMS)?;
+
+    // Every string starting with the key sorts between the key itself and the key followed by the largest character
+    let range = KeyRange::bound(
+        &JsValue::from(key.as_str()),
+        &JsValue::from(format!("{key}\u{ffff}")),
+        false,
+        false,
+    )?;
+
+    let mut expressions = Vec::new();
+    for index in indices {
+        let matches = terms
+            .index(index)?
+            .get_all(
+                Some(&range),
+                Some(u32::try_from(limit).unwrap_or(u32::MAX)),
+                None,
+                None,
+            )
+            .await?;
+
+        for (_, value) in matches {
+            let term: IndexedTerm = serde_wasm_bindgen::from_value(value)?;
+            if !expressions.contains(&term.expression) {
+                expressions.push(term.expression);
+            }
+        }
+    }
+
+    expressions.truncate(limit);
+
+    Ok(expressions)
+}
+
+/// Reduces a glossary or query to its core meaning for comparing them.
+fn core_meaning(text: &str) -> String {
+    let text = text.trim().to_lowercase();
+
+    // Glosses of verbs start with "to", and details are given in parentheses
+    let text = text.trim_start_matches("to ");
+    text.split(" (")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// How well a glossary matches a reverse search, where higher is better.
+fn match_quality(gloss: &str, query: &str) -> u8 {
+    let gloss = core_meaning(gloss);
+    let query = core_meaning(query);
+    let (gloss, query) = (gloss.as_str(), query.as_str());
+
+    if query.is_empty() {
+        0
+    } else if gloss == query {
+        3
+    } else if gloss.starts_with(query) {
+        2
+    } else if gloss.contains(query) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Finds up to `limit` expressions whose glossary contains the words of the English `query`,
+/// ranked by how well they match and how frequent they are.
+pub async fn find_reverse(query: &str, limit: usize) -> Result<Vec<String>, IndexError> {
+    let query = query.trim().to_lowercase();
+    let query_words = words(&query);
+    if query_words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let db = get_index_db().await?;
+    let transaction = db.transaction(&[GLOSSARY, FREQUENCIES], TransactionMode::ReadOnly)?;
+    let glossary = transaction.store(GLOSSARY)?;
+    let frequencies = transaction.store(FREQUENCIES)?;
+    let word_index = glossary.index("word")?;
+
+    // Every match contains all words, so the rarest one finds all of them with the fewest rows
+    let mut rarest = None;
+    for word in &query_words {
+        let range = KeyRange::only(&JsValue::from(word.as_str()))?;
+        let count = word_index.count(Some(&range)).await?;
+        if rarest.as_ref().map_or(true, |(_, fewest)| count < *fewest) {
+            rarest = Some((range, count));
+        }
+    }
+    let Some((range, _)) = rarest else {
+        return Ok(Vec::new());
+    };
+
+    let candidates = word_index.get_all(Some(&range), None, None, None).await?;
+
+    // Best match of every expression as (quality, score)
+    let mut matches: Vec<(String, u8, i64)> = Vec::new();
+    for (_, value) in candidates {
+        let word: GlossaryWord = serde_wasm_bindgen::from_value(value)?;
+
+        let gloss_words = words(&word.gloss);
+        if !query_words.iter().all(|w| gloss_words.contains(w)) {
+            continue;
+        }
+
+        let quality = match_quality(&word.gloss, &query);
+        match matches.iter_mut().find(|(e, _, _)| *e == word.expression) {
+            Some(existing) if existing.1 < quality => {
+                *existing = (word.expression, quality, word.score);
+            }
+            Some(_) => {}
+            None => matches.push((word.expression, quality, word.score)),
+        }
+    }
+
+    let mut ranked = Vec::new();
+    for (expression, quality, score) in matches {
+        let frequency = frequencies.get(&JsValue::from(expression.as_str())).await?;
+        let rank = if frequency.is_undefined() {
+            u64::MAX
+        } else {
+            serde_wasm_bindgen::from_value::<Frequency>(frequency)?.rank
+        };
+
+        ranked.push((expression, quality, rank, score));
+    }
+
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)).then(b.3.cmp(&a.3)));
+
+    Ok(ranked
+        .into_iter()
+        .take(limit)
+        .map(|(expression, _, _, _)| expression)
+        .collect())
+}
+
+/// Frequency ranks of the given expressions, leaving out those without any.
+pub async fn frequency_ranks(
+    expressions: impl IntoIterator<Item = &str>,
+) -> Result<HashMap<String, u64>, IndexError> {
+    let db = get_index_db().await?;
+    let transaction = db.transaction(&[FREQUENCIES], TransactionMode::ReadOnly)?;
+    let frequencies = transaction.store(FREQUENCIES)?;
+
+    let mut ranks = HashMap::new();
+    for expression in expressions {
+        let frequency = frequencies.get(&JsValue::from(expression)).await?;
+        if !frequency.is_undefined() {
+            let frequency: Frequency = serde_wasm_bindgen::from_value(frequency)?;
+            ranks.insert(frequency.expression, frequency.rank);
+        }
+    }
+
+    Ok(ranks)
+}
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("An error occured with the rexie IndexedDB backend: `{0}`")]
+    Rexie(#[from] rexie::Error),
+    #[error("Error parsing JSObject: `{0}`")]
+    Jsobj(#[from] serde_wasm_bindgen::Error),
+    #[error("Error reading dictionary archive: `{0}`")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Error reading dictionary file: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Error parsing dictionary JSON: `{0}`")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(words("To eat (food), to EAT"), ["eat", "food"]);
+        assert_eq!(words("the day's end"), ["day's", "end"]);
+    }
+
+    #[test]
+    fn ranks_exact_matches_first() {
+        assert_eq!(match_quality("to eat", "to eat"), 3);
+        assert_eq!(match_quality("to eat (food)", "to eat"), 3);
+        assert_eq!(match_quality("To eat", "eat"), 3);
+        assert_eq!(match_quality("to eat up", "to eat"), 2);
+        assert_eq!(match_quality("to overeat", "eat"), 1);
+        assert_eq!(match_quality("to drink", "eat"), 0);
+    }
+}
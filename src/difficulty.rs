@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    analysis::{BookAnalysis, ChapterAnalysis},
+    scan, segment,
+    user_db::Difficulty,
+};
+
+// Characters on a printed page of a Japanese novel, roughly
+const PAGE_CHARS: f64 = 600.0;
+// Words less frequent than this are unlikely to be known by learners
+const RARE_RANK: u64 = 10_000;
+
+impl Difficulty {
+    /// One line describing the difficulty, for lists of chapters or books.
+    pub fn summary(&self) -> String {
+        let rare = self
+            .rare_share
+            .map_or_else(String::new, |rare| format!(" · {rare:.0}% rare"));
+
+        format!(
+            "{:.0}% known{rare} · {} kanji · {:.0} characters per sentence · {:.1} lookups per page",
+            self.known_coverage,
+            self.unique_kanji,
+            self.sentence_length,
+            self.lookups_per_page,
+        )
+    }
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Computes the difficulty of the given chapters together.
+#[allow(clippy::cast_precision_loss)]
+pub fn difficulty<'a>(
+    chapters: impl IntoIterator<Item = &'a ChapterAnalysis>,
+    known: &HashSet<String>,
+    ranks: &HashMap<String, u64>,
+) -> Difficulty {
+    let mut tokens = 0;
+    let mut known_tokens = 0;
+    let mut ranked_tokens = 0;
+    let mut rare_tokens = 0;
+    let mut kanji = HashSet::new();
+    let mut sentences = 0;
+    let mut sentence_chars = 0;
+    let mut chars = 0;
+    let mut unknown_words = HashSet::new();
+
+    for chapter in chapters {
+        for token in &chapter.tokens {
+            tokens += 1;
+
+            if known.contains(&token.expression) {
+                known_tokens += 1;
+            } else {
+                unknown_words.insert(token.expression.as_str());
+            }
+
+            if let Some(&rank) = ranks.get(&token.expression) {
+                ranked_tokens += 1;
+                if rank > RARE_RANK {
+                    rare_tokens += 1;
+                }
+            }
+        }
+
+        kanji.extend(chapter.chars.iter().copied().filter(|&c| is_kanji(c)));
+
+        // Only count the Japanese text, not whitespace or punctuation on their own
+        for (start, end) in scan::sentence_spans(&chapter.chars) {
+            let length = chapter.chars[start..end]
+                .iter()
+                .filter(|&&c| segment::is_japanese(c))
+                .count();
+            if length > 0 {
+                sentences += 1;
+                sentence_chars += length;
+                chars += length;
+            }
+        }
+    }
+
+    let pages = (chars as f64 / PAGE_CHARS).max(1.0);
+
+    Difficulty {
+        known_coverage: percent(known_tokens, tokens),
+        rare_share: (ranked_tokens > 0).then(|| percent(rare_tokens, ranked_tokens)),
+        unique_kanji: kanji.len(),
+        sentence_length: if sentences == 0 {
+            0.0
+        } else {
+            sentence_chars as f64 / sentences as f64
+        },
+        lookups_per_page: unknown_words.len() as f64 / pages,
+    }
+}
+
+impl BookAnalysis {
+    pub fn chapter_difficulty(&self, page: usize, known: &HashSet<String>) -> Option<Difficulty> {
+        Some(difficulty([self.chapter(page)?], known, &self.ranks))
+    }
+
+    pub fn book_difficulty(&self, known: &HashSet<String>) -> Difficulty {
+        difficulty(&self.chapters, known, &self.ranks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::Token;
+
+    fn chapter(text: &str, words: &[&str]) -> ChapterAnalysis {
+        ChapterAnalysis {
+            page: 0,
+            title: String::new(),
+            chars: text.chars().collect(),
+            tokens: words
+                .iter()
+                .map(|w| Token {
+                    offset: 0,
+                    len: w.chars().count(),
+                    expression: (*w).to_string(),
+                    reading: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn counts_known_words_and_kanji() {
+        let chapter = chapter("猫が好き。犬も好き。", &["猫", "好き", "犬", "好き"]);
+        let known = HashSet::from(["好き".to_string()]);
+
+        let d = difficulty([&chapter], &known, &HashMap::new());
+
+        assert_close(d.known_coverage, 50.0);
+        assert_eq!(d.unique_kanji, 3);
+        assert_close(d.sentence_length, 4.0);
+        // Two new words on less than a page
+        assert_close(d.lookups_per_page, 2.0);
+    }
+
+    #[test]
+    fn leaves_unranked_words_out_of_the_rare_share() {
+        let chapter = chapter("猫と犬と鼬", &["猫", "犬", "鼬"]);
+        let ranks = HashMap::from([("猫".to_string(), 100), ("犬".to_string(), 20_000)]);
+
+        let d = difficulty([&chapter], &HashSet::new(), &ranks);
+        assert_close(d.rare_share.unwrap_or_default(), 50.0);
+
+        let d = difficulty([&chapter], &HashSet::new(), &HashMap::new());
+        assert!(d.rare_share.is_none());
+        assert!(!d.summary().contains("rare"));
+    }
+}
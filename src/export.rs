@@ -0,0 +1,131 @@
+extern crate web_sys;
+
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+
+// Milliseconds the file of a download is kept after starting it
+const REVOKE_DELAY: i32 = 10 * 1000;
+
+/// Lets the user save `content` as a file called `filename`.
+pub fn download(filename: &str, mime: &str, content: &str) {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let parts = js_sys::Array::new();
+    parts.push(&content.into());
+
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_(mime);
+
+    let Ok(blob) = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options) else {
+        log::error!("Cannot create file {filename} for download");
+        return;
+    };
+    let Ok(url) = web_sys::Url::create_object_url_with_blob(&blob) else {
+        log::error!("Cannot create link to {filename} for download");
+        return;
+    };
+
+    // Downloads are started by clicking a link to the file
+    let link = document
+        .create_element("a")
+        .ok()
+        .and_then(|e| e.dyn_into::<web_sys::HtmlAnchorElement>().ok());
+    if let Some(link) = link {
+        link.set_href(&url);
+        link.set_download(filename);
+        link.click();
+    }
+
+    // The download may only start after the click was handled, so the file must stay around a bit
+    let revoke = Closure::once_into_js(move || {
+        web_sys::Url::revoke_object_url(&url).ok();
+    });
+    window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), REVOKE_DELAY)
+        .ok();
+}
+
+/// Escapes a field for tab separated values, which Anki can import.
+pub fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\r', '\n'], " ")
+}
+
+/// Joins rows of fields to tab separated values.
+pub fn tsv(rows: impl IntoIterator<Item = Vec<String>>) -> String {
+    rows.into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|field| tsv_field(field))
+                .collect::<Vec<_>>()
+                .join("\t")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Joins rows of fields to comma separated values.
+pub fn csv(rows: impl IntoIterator<Item = Vec<String>>) -> String {
+    rows.into_iter()
+        .map(|row| {
+            row.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats milliseconds since the epoch as local date and time.
+pub fn format_timestamp(timestamp: f64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(timestamp))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(ToString::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn joins_tsv() {
+        assert_eq!(tsv(rows(&[&["a", "b"], &["c", "d"]])), "a\tb\nc\td");
+    }
+
+    #[test]
+    fn escapes_tsv_fields() {
+        assert_eq!(tsv_field("a\tb\r\nc"), "a b  c");
+        assert_eq!(
+            tsv(rows(&[&["食べる", "to eat\tto consume"]])),
+            "食べる\tto eat to consume"
+        );
+    }
+
+    #[test]
+    fn joins_csv() {
+        assert_eq!(csv(rows(&[&["a", "b"], &["c", "d"]])), "a,b\nc,d");
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("to see, to look"), "\"to see, to look\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+}
@@ -0,0 +1,56 @@
+extern crate web_sys;
+
+use js_sys::{Array, Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Highlight marking the text of the current lookup.
+pub const MATCH: &str = "yomi-match";
+/// Highlight marking words the user doesn't know yet.
+pub const UNKNOWN: &str = "yomi-unknown";
+
+// The CSS Custom Highlight API isn't covered by web_sys yet, so we go through reflection.
+// Unlike changing the selection or wrapping text in elements, this leaves the DOM alone.
+fn registry() -> Option<JsValue> {
+    let css = Reflect::get(&js_sys::global(), &JsValue::from("CSS")).ok()?;
+    let highlights = Reflect::get(&css, &JsValue::from("highlights")).ok()?;
+
+    (!highlights.is_undefined()).then_some(highlights)
+}
+
+fn call(target: &JsValue, method: &str, args: &Array) -> Result<JsValue, JsValue> {
+    let function = Reflect::get(target, &JsValue::from(method))?.dyn_into::<Function>()?;
+    Reflect::apply(&function, target, args)
+}
+
+/// Highlights `ranges` under `name`, replacing whatever was highlighted under that name before.
+pub fn set(name: &str, ranges: &[web_sys::Range]) {
+    let Some(registry) = registry() else {
+        log::warn!("Highlights are not supported by this browser");
+        return;
+    };
+
+    let highlight = Reflect::get(&js_sys::global(), &JsValue::from("Highlight"))
+        .and_then(|constructor| constructor.dyn_into::<Function>())
+        .and_then(|constructor| {
+            Reflect::construct(&constructor, &ranges.iter().collect::<Array>())
+        });
+
+    let result = highlight.and_then(|highlight| {
+        call(
+            &registry,
+            "set",
+            &Array::of2(&JsValue::from(name), &highlight),
+        )
+    });
+
+    if let Err(e) = result {
+        log::error!("Failed to set highlight {name}: {e:?}");
+    }
+}
+
+/// Removes the highlight `name`.
+pub fn clear(name: &str) {
+    if let Some(registry) = registry() {
+        call(&registry, "delete", &Array::of1(&JsValue::from(name))).ok();
+    }
+}
@@ -0,0 +1,246 @@
+use dioxus::prelude::*;
+
+use crate::{
+    definitions::Lookup,
+    export,
+    read_state::ReaderState,
+    user_db::{self, HistoryEntry, MiningEntry},
+};
+
+fn matches(entry: &HistoryEntry, query: &str, book: &str) -> bool {
+    (book.is_empty() || entry.book == book)
+        && (query.is_empty()
+            || [
+                &entry.expression,
+                &entry.reading,
+                &entry.text,
+                &entry.sentence,
+            ]
+            .iter()
+            .any(|field| field.contains(query)))
+}
+
+fn export_history(entries: &[&HistoryEntry]) {
+    let header = [
+        "Expression",
+        "Reading",
+        "Sentence",
+        "Book",
+        "Chapter",
+        "Date",
+    ]
+    .map(ToString::to_string)
+    .to_vec();
+    let rows = entries.iter().map(|e| {
+        vec![
+            e.expression.clone(),
+            e.reading.clone(),
+            e.sentence.clone(),
+            e.book.clone(),
+            (e.chapter + 1).to_string(),
+            export::format_timestamp(e.timestamp),
+        ]
+    });
+
+    export::download(
+        "history.tsv",
+        "text/tab-separated-values",
+        &export::tsv(std::iter::once(header).chain(rows)),
+    );
+}
+
+/// Adds the best match of the last of `lookups` to the history, as found from `text`.
+/// The open book is noted even if the lookup didn't start from it.
+pub(crate) async fn record(
+    lookups: &UseRef<Vec<Lookup>>,
+    read_state: &UseRef<Option<ReaderState>>,
+    text: String,
+    sentence: String,
+    offset: Option<usize>,
+) {
+    let Some((expression, reading, glossary)) = lookups.with(|lookups| {
+        let d = lookups.last()?.entries.first()?;
+        let glossary = d
+            .entries
+            .iter()
+            .flat_map(|e| e.term.glossary.iter().cloned())
+            .collect::<Vec<_>>();
+        Some((d.expression.clone(), d.reading.clone(), glossary))
+    }) else {
+        return;
+    };
+    let (book, chapter) = read_state
+        .with(|state| state.as_ref().map(|s| (s.get_title(), s.get_page())))
+        .unwrap_or_default();
+
+    let entry = HistoryEntry {
+        id: None,
+        expression,
+        reading,
+        glossary,
+        text,
+        sentence,
+        book,
+        chapter,
+        offset,
+        timestamp: js_sys::Date::now(),
+    };
+
+    if let Err(e) = user_db::add_history(&entry).await {
+        log::error!(
+            "Cannot add {} to history due to error {e}",
+            entry.expression
+        );
+    }
+}
+
+async fn mine(entry: HistoryEntry) {
+    let mined = MiningEntry {
+        id: None,
+        expression: entry.expression,
+        reading: entry.reading,
+        glossary: entry.glossary,
+        sentence: entry.sentence,
+        book: entry.book,
+        timestamp: js_sys::Date::now(),
+    };
+
+    if let Err(e) = user_db::add_mining(&mined).await {
+        log::error!("Cannot add {} to mining due to error {e}", mined.expression);
+    }
+}
+
+#[derive(Props)]
+pub struct HistoryProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+}
+
+pub fn history_component<'a>(cx: Scope<'a, HistoryProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+
+    let query = use_state(cx, String::new);
+    let book = use_state(cx, String::new);
+    // Ids of entries sent to mining, so they aren't sent twice by accident
+    let mined = use_ref(cx, Vec::<u32>::new);
+
+    let history = use_future(cx, (), |()| async move { user_db::get_history().await });
+
+    let current_book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let history = match history.value() {
+        None => return cx.render(rsx!(p{"Loading history"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load history: {e}"}));
+        }
+        Some(Ok(history)) => history,
+    };
+
+    let mut books = history.iter().map(|e| e.book.as_str()).collect::<Vec<_>>();
+    books.sort_unstable();
+    books.dedup();
+
+    let filtered = history
+        .iter()
+        .filter(|e| matches(e, query.get(), book.get()))
+        .collect::<Vec<_>>();
+    let count = filtered.len();
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-wrap gap-2 mt-2",
+
+            input{
+                class: "flex-1 min-w-0 px-2 rounded border-2",
+
+                r#type: "search",
+                placeholder: "Search history",
+                value: "{query}",
+                oninput: move |evt: FormEvent| query.set(evt.value.clone()),
+            }
+
+            select{
+                class: "bg-gray-100 rounded p-1",
+
+                value: "{book}",
+                onchange: move |evt: FormEvent| book.set(evt.value.clone()),
+
+                option{ value: "", "All books" }
+                books.iter().map(|b| rsx!(option{ key: "{b}", value: "{b}", "{b}" }))
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_history(&filtered),
+
+                "Export"
+            }
+        }
+
+        p{
+            class: "text-sm text-gray-600 my-1",
+
+            "{count} lookups"
+        }
+
+        ul{
+            class: "list-none",
+
+            history.iter().filter(|e| matches(e, query.get(), book.get())).map(|entry| {
+                let id = entry.id.unwrap_or_default();
+                let date = export::format_timestamp(entry.timestamp);
+                let chapter = entry.chapter + 1;
+                // Only the open book can be jumped to
+                let elsewhere = current_book.as_deref() != Some(entry.book.as_str());
+                let is_mined = mined.read().contains(&id);
+
+                rsx!(li{
+                    key: "{id}",
+                    class: "border-b py-1",
+
+                    h2{
+                        class: "text-lg font-medium inline-block mr-2",
+
+                        ruby {
+                            "{entry.expression}"
+                            rt{ "{entry.reading}" }
+                        }
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300 disabled:opacity-50",
+                        disabled: "{elsewhere}",
+
+                        onclick: move |_| read_state.with_mut(|state| {
+                            if let Some(state) = state.as_mut() {
+                                state.jump_to(entry.chapter, entry.offset);
+                            }
+                        }),
+
+                        "Go to"
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300 disabled:opacity-50",
+                        disabled: "{is_mined}",
+
+                        onclick: move |_| {
+                            mined.with_mut(|m| m.push(id));
+                            wasm_bindgen_futures::spawn_local(mine(entry.clone()));
+                        },
+
+                        "Mine"
+                    }
+
+                    p{ "{entry.sentence}" }
+                    p{
+                        class: "text-sm text-gray-600",
+
+                        "{entry.book} · Chapter {chapter} · {date}"
+                    }
+                })
+            })
+        }
+    })
+}
@@ -10,4 +10,5 @@ pub enum LoadDictState {
     ParsingDict,
     AddingDictIndex,
     AddingDictContent(usize, usize),
+    BuildingIndex(usize, usize),
 }
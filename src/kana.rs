@@ -0,0 +1,379 @@
+const ROMAJI: &[(&str, &str)] = &[
+    ("a", "あ"),
+    ("i", "い"),
+    ("u", "う"),
+    ("e", "え"),
+    ("o", "お"),
+    ("ka", "か"),
+    ("ki", "き"),
+    ("ku", "く"),
+    ("ke", "け"),
+    ("ko", "こ"),
+    ("sa", "さ"),
+    ("shi", "し"),
+    ("si", "し"),
+    ("su", "す"),
+    ("se", "せ"),
+    ("so", "そ"),
+    ("ta", "た"),
+    ("chi", "ち"),
+    ("ti", "ち"),
+    ("tsu", "つ"),
+    ("tu", "つ"),
+    ("te", "て"),
+    ("to", "と"),
+    ("na", "な"),
+    ("ni", "に"),
+    ("nu", "ぬ"),
+    ("ne", "ね"),
+    ("no", "の"),
+    ("ha", "は"),
+    ("hi", "ひ"),
+    ("fu", "ふ"),
+    ("hu", "ふ"),
+    ("he", "へ"),
+    ("ho", "ほ"),
+    ("ma", "ま"),
+    ("mi", "み"),
+    ("mu", "む"),
+    ("me", "め"),
+    ("mo", "も"),
+    ("ya", "や"),
+    ("yu", "ゆ"),
+    ("yo", "よ"),
+    ("ra", "ら"),
+    ("ri", "り"),
+    ("ru", "る"),
+    ("re", "れ"),
+    ("ro", "ろ"),
+    ("wa", "わ"),
+    ("wi", "うぃ"),
+    ("we", "うぇ"),
+    ("wo", "を"),
+    ("ga", "が"),
+    ("gi", "ぎ"),
+    ("gu", "ぐ"),
+    ("ge", "げ"),
+    ("go", "ご"),
+    ("za", "ざ"),
+    ("ji", "じ"),
+    ("zi", "じ"),
+    ("zu", "ず"),
+    ("ze", "ぜ"),
+    ("zo", "ぞ"),
+    ("da", "だ"),
+    ("di", "ぢ"),
+    ("du", "づ"),
+    ("de", "で"),
+    ("do", "ど"),
+    ("ba", "ば"),
+    ("bi", "び"),
+    ("bu", "ぶ"),
+    ("be", "べ"),
+    ("bo", "ぼ"),
+    ("pa", "ぱ"),
+    ("pi", "ぴ"),
+    ("pu", "ぷ"),
+    ("pe", "ぺ"),
+    ("po", "ぽ"),
+    ("va", "ゔぁ"),
+    ("vi", "ゔぃ"),
+    ("vu", "ゔ"),
+    ("ve", "ゔぇ"),
+    ("vo", "ゔぉ"),
+    ("fa", "ふぁ"),
+    ("fi", "ふぃ"),
+    ("fe", "ふぇ"),
+    ("fo", "ふぉ"),
+    ("kya", "きゃ"),
+    ("kyu", "きゅ"),
+    ("kyo", "きょ"),
+    ("sha", "しゃ"),
+    ("shu", "しゅ"),
+    ("she", "しぇ"),
+    ("sho", "しょ"),
+    ("sya", "しゃ"),
+    ("syu", "しゅ"),
+    ("syo", "しょ"),
+    ("cha", "ちゃ"),
+    ("chu", "ちゅ"),
+    ("che", "ちぇ"),
+    ("cho", "ちょ"),
+    ("tya", "ちゃ"),
+    ("tyu", "ちゅ"),
+    ("tyo", "ちょ"),
+    ("thi", "てぃ"),
+    ("nya", "にゃ"),
+    ("nyu", "にゅ"),
+    ("nyo", "にょ"),
+    ("hya", "ひゃ"),
+    ("hyu", "ひゅ"),
+    ("hyo", "ひょ"),
+    ("mya", "みゃ"),
+    ("myu", "みゅ"),
+    ("myo", "みょ"),
+    ("rya", "りゃ"),
+    ("ryu", "りゅ"),
+    ("ryo", "りょ"),
+    ("gya", "ぎゃ"),
+    ("gyu", "ぎゅ"),
+    ("gyo", "ぎょ"),
+    ("ja", "じゃ"),
+    ("ju", "じゅ"),
+    ("je", "じぇ"),
+    ("jo", "じょ"),
+    ("jya", "じゃ"),
+    ("jyu", "じゅ"),
+    ("jyo", "じょ"),
+    ("zya", "じゃ"),
+    ("zyu", "じゅ"),
+    ("zyo", "じょ"),
+    ("dya", "ぢゃ"),
+    ("dyu", "ぢゅ"),
+    ("dyo", "ぢょ"),
+    ("dhi", "でぃ"),
+    ("bya", "びゃ"),
+    ("byu", "びゅ"),
+    ("byo", "びょ"),
+    ("pya", "ぴゃ"),
+    ("pyu", "ぴゅ"),
+    ("pyo", "ぴょ"),
+    ("xa", "ぁ"),
+    ("xi", "ぃ"),
+    ("xu", "ぅ"),
+    ("xe", "ぇ"),
+    ("xo", "ぉ"),
+    ("xya", "ゃ"),
+    ("xyu", "ゅ"),
+    ("xyo", "ょ"),
+    ("xtu", "っ"),
+    ("xtsu", "っ"),
+    ("la", "ぁ"),
+    ("li", "ぃ"),
+    ("lu", "ぅ"),
+    ("le", "ぇ"),
+    ("lo", "ぉ"),
+    ("lya", "ゃ"),
+    ("lyu", "ゅ"),
+    ("lyo", "ょ"),
+    ("ltu", "っ"),
+    ("ltsu", "っ"),
+];
+
+const LONGEST_ROMAJI: usize = 4;
+
+// Offset between the hiragana and katakana blocks
+const KATAKANA_OFFSET: u32 = 0x60;
+
+const fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+fn is_hiragana(c: char) -> bool {
+    ('\u{3041}'..='\u{3096}').contains(&c)
+}
+
+fn is_katakana(c: char) -> bool {
+    ('\u{30a1}'..='\u{30f6}').contains(&c)
+}
+
+/// Converts hiragana to katakana, leaving everything else as is.
+pub fn hiragana_to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if is_hiragana(c) {
+                char::from_u32(u32::from(c) + KATAKANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Converts katakana to hiragana, leaving everything else as is.
+pub fn katakana_to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if is_katakana(c) {
+                char::from_u32(u32::from(c) - KATAKANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Converts romaji to hiragana, or katakana where it is typed in upper case.
+/// Everything that isn't romaji is kept as is.
+///
+/// Like an IME, trailing romaji that may still become kana is left alone unless `complete` is set,
+/// so this can be applied while the user is typing.
+pub fn romaji_to_kana(input: &str, complete: bool) -> String {
+    let chars = input.chars().collect::<Vec<_>>();
+    let lower = input
+        .chars()
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    let mut kana = String::new();
+    let mut push = |text: &str, katakana: bool| {
+        if katakana {
+            kana.push_str(&hiragana_to_katakana(text));
+        } else {
+            kana.push_str(text);
+        }
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = lower[i];
+        let katakana = chars[i].is_ascii_uppercase();
+        let next = lower.get(i + 1).copied();
+
+        if c == '-' {
+            push("ー", false);
+            i += 1;
+            continue;
+        }
+
+        if !c.is_ascii_alphabetic() {
+            push(&chars[i].to_string(), false);
+            i += 1;
+            continue;
+        }
+
+        // A doubled consonant is a small tsu
+        if c != 'n' && !is_vowel(c) && next == Some(c) {
+            push("っ", katakana);
+            i += 1;
+            continue;
+        }
+
+        if c == 'n' {
+            match next {
+                None if !complete => {
+                    push(&chars[i].to_string(), false);
+                    i += 1;
+                    continue;
+                }
+                None => {
+                    push("ん", katakana);
+                    i += 1;
+                    continue;
+                }
+                Some('\'') => {
+                    push("ん", katakana);
+                    i += 2;
+                    continue;
+                }
+                // "nn" may still be followed by a vowel, making it ん and the start of な
+                Some('n') if !complete && i + 2 == chars.len() => {
+                    push(&chars[i..].iter().collect::<String>(), false);
+                    break;
+                }
+                // In "nna", the first n is ん and the rest is な, but "nn" on its own is ん too
+                Some('n') => {
+                    let before_vowel = lower.get(i + 2).map_or(false, |&c| is_vowel(c) || c == 'y');
+                    push("ん", katakana);
+                    i += if before_vowel { 1 } else { 2 };
+                    continue;
+                }
+                Some(next) if !is_vowel(next) && next != 'y' => {
+                    push("ん", katakana);
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        let converted = (1..=LONGEST_ROMAJI.min(chars.len() - i))
+            .rev()
+            .find_map(|len| {
+                let chunk = lower[i..i + len].iter().collect::<String>();
+                ROMAJI
+                    .iter()
+                    .find(|(romaji, _)| *romaji == chunk)
+                    .map(|(_, kana)| (len, *kana))
+            });
+
+        if let Some((len, text)) = converted {
+            push(text, katakana);
+            i += len;
+            continue;
+        }
+
+        // The rest may still become kana once the user continues typing
+        let rest = lower[i..].iter().collect::<String>();
+        if !complete && ROMAJI.iter().any(|(romaji, _)| romaji.starts_with(&rest)) {
+            push(&chars[i..].iter().collect::<String>(), false);
+            break;
+        }
+
+        push(&chars[i].to_string(), false);
+        i += 1;
+    }
+
+    kana
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nn_before_vowel() {
+        assert_eq!(romaji_to_kana("konnichiha", true), "こんにちは");
+        assert_eq!(romaji_to_kana("onna", true), "おんな");
+        assert_eq!(romaji_to_kana("konnyaku", true), "こんにゃく");
+    }
+
+    #[test]
+    fn n_apostrophe() {
+        assert_eq!(romaji_to_kana("kan'i", true), "かんい");
+        assert_eq!(romaji_to_kana("kani", true), "かに");
+    }
+
+    #[test]
+    fn trailing_n() {
+        assert_eq!(romaji_to_kana("hon", true), "ほん");
+        assert_eq!(romaji_to_kana("honn", true), "ほん");
+        assert_eq!(romaji_to_kana("hon", false), "ほn");
+        assert_eq!(romaji_to_kana("honn", false), "ほnn");
+    }
+
+    #[test]
+    fn small_tsu() {
+        assert_eq!(romaji_to_kana("kitte", true), "きって");
+        assert_eq!(romaji_to_kana("zasshi", true), "ざっし");
+    }
+
+    #[test]
+    fn katakana() {
+        assert_eq!(romaji_to_kana("KATAKANA", true), "カタカナ");
+        assert_eq!(romaji_to_kana("KO-HI-", true), "コーヒー");
+        assert_eq!(hiragana_to_katakana("ひらがな"), "ヒラガナ");
+        assert_eq!(katakana_to_hiragana("カタカナ"), "かたかな");
+    }
+
+    #[test]
+    fn incremental_input() {
+        // Each step converts what was typed so far, like the input field does
+        let mut typed = String::new();
+        for c in "konnichiha".chars() {
+            typed.push(c);
+            typed = romaji_to_kana(&typed, false);
+        }
+        assert_eq!(typed, "こんにちは");
+
+        let mut typed = String::new();
+        for c in "onna".chars() {
+            typed.push(c);
+            typed = romaji_to_kana(&typed, false);
+        }
+        assert_eq!(typed, "おんな");
+
+        assert_eq!(romaji_to_kana("ky", false), "ky");
+        assert_eq!(romaji_to_kana("ky", true), "ky");
+    }
+}
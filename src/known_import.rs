@@ -0,0 +1,509 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{
+    definitions::get_terms,
+    kana, known_words,
+    segment::is_japanese,
+    upload_component::upload_component,
+    user_db::{self, KnownWord},
+};
+
+// Rows shown to help choosing the columns
+const PREVIEW_ROWS: usize = 3;
+
+/// Splits a line of comma separated values, taking quotes into account.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+fn json_field(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Reads a JSON array of words, of arrays or of objects into rows and their column names.
+fn parse_json(text: &str) -> Option<(Vec<String>, Vec<Vec<String>>)> {
+    let items: Vec<serde_json::Value> = serde_json::from_str(text).ok()?;
+
+    let mut columns = Vec::<String>::new();
+    for item in &items {
+        if let serde_json::Value::Object(object) = item {
+            for key in object.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+    }
+
+    let rows = items
+        .iter()
+        .map(|item| match item {
+            serde_json::Value::Array(fields) => fields.iter().map(json_field).collect(),
+            serde_json::Value::Object(object) => columns
+                .iter()
+                .map(|c| object.get(c).map(json_field).unwrap_or_default())
+                .collect(),
+            other => vec![json_field(other)],
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    Some((columns, rows))
+}
+
+/// Reads an Anki export, a CSV or TSV file, JSON or a plain list of words into rows.
+/// Returns column names, which are taken from the first row if `has_header` and made up if the file has none.
+fn parse(text: &str, has_header: bool) -> (Vec<String>, Vec<Vec<String>>) {
+    let trimmed = text.trim_start_matches('\u{feff}').trim();
+
+    let (columns, rows) = if trimmed.starts_with('[') {
+        parse_json(trimmed).unwrap_or_default()
+    } else {
+        // Anki exports start with comments like #separator:tab
+        let lines = trimmed
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+            .collect::<Vec<_>>();
+
+        let rows = if lines.iter().any(|l| l.contains('\t')) {
+            lines
+                .iter()
+                .map(|l| l.split('\t').map(ToString::to_string).collect())
+                .collect()
+        } else if !lines.is_empty() && lines.iter().all(|l| l.contains(',')) {
+            lines.iter().map(|l| split_csv(l)).collect()
+        } else {
+            lines.iter().map(|l| vec![l.trim().to_string()]).collect()
+        };
+
+        (Vec::new(), rows)
+    };
+
+    // Objects in JSON name their columns already
+    let (mut columns, rows) = match rows.split_first() {
+        Some((header, rest)) if has_header && columns.is_empty() => (
+            header.iter().map(|c| c.trim().to_string()).collect(),
+            rest.to_vec(),
+        ),
+        _ => (columns, rows),
+    };
+    for (i, column) in columns.iter_mut().enumerate() {
+        if column.is_empty() {
+            *column = format!("Column {}", i + 1);
+        }
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+    for i in columns.len()..width {
+        columns.push(format!("Column {}", i + 1));
+    }
+
+    (columns, rows)
+}
+
+/// Whether the first row names the columns, like "Expression,Reading", rather than being a word.
+fn looks_like_header(rows: &[Vec<String>]) -> bool {
+    let japanese = |row: &Vec<String>| row.iter().any(|field| field.chars().any(is_japanese));
+
+    match rows.split_first() {
+        Some((first, rest)) => !japanese(first) && rest.iter().any(japanese),
+        None => false,
+    }
+}
+
+thread_local! {
+    static MARKUP: regex::Regex = regex::Regex::new(r"<[^>]*>|&nbsp;|\[[^\]]*\]").unwrap();
+}
+
+/// Removes markup and Anki's furigana like 食[た]べる from a field.
+fn clean(field: &str) -> String {
+    MARKUP.with(|markup| {
+        markup
+            .replace_all(field, "")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect()
+    })
+}
+
+/// Finds the dictionary entry for a word, preferring the one with the given reading.
+async fn match_word(
+    word: &str,
+    reading: &str,
+    reasons: &yomi_dict::Reasons,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+) -> Option<KnownWord> {
+    let entries = get_terms(word, reasons, db).await.ok()?;
+    let reading = kana::katakana_to_hiragana(reading);
+
+    // Only entries for the whole word, not inflections or shorter words
+    let candidates = entries
+        .iter()
+        .filter(|d| d.expression == word || d.reading == word)
+        .collect::<Vec<_>>();
+
+    let matched = candidates
+        .iter()
+        .find(|d| reading.is_empty() || kana::katakana_to_hiragana(&d.reading) == reading)
+        .or_else(|| candidates.first())?;
+
+    Some(KnownWord {
+        expression: matched.expression.clone(),
+        reading: matched.reading.clone(),
+        timestamp: js_sys::Date::now(),
+    })
+}
+
+/// Outcome of an import.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ImportState {
+    Idle,
+    Importing(usize, usize),
+    Done { imported: usize, unmatched: usize },
+}
+
+struct ImportOptions {
+    word_column: usize,
+    reading_column: Option<usize>,
+    dictionary_only: bool,
+}
+
+async fn import(
+    rows: Vec<Vec<String>>,
+    options: ImportOptions,
+    known: UseRef<HashSet<String>>,
+    state: UseRef<ImportState>,
+    db: UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: UseState<yomi_dict::Reasons>,
+) {
+    // Without it every word would count as unmatched
+    if db.read().is_none() {
+        log::error!("Cannot import known words since DB is not loaded yet!");
+        state.set(ImportState::Idle);
+        return;
+    }
+
+    let mut words = Vec::new();
+    let mut unmatched = 0;
+
+    for (i, row) in rows.iter().enumerate() {
+        if i % 50 == 0 {
+            state.set(ImportState::Importing(i, rows.len()));
+        }
+
+        let word = clean(row.get(options.word_column).map_or("", String::as_str));
+        if word.is_empty() {
+            continue;
+        }
+        let reading = options
+            .reading_column
+            .and_then(|c| row.get(c))
+            .map(|r| clean(r))
+            .unwrap_or_default();
+
+        match match_word(&word, &reading, reasons.get(), &db).await {
+            Some(matched) => words.push(matched),
+            None if options.dictionary_only => unmatched += 1,
+            None => {
+                unmatched += 1;
+                words.push(KnownWord {
+                    expression: word,
+                    reading,
+                    timestamp: js_sys::Date::now(),
+                });
+            }
+        }
+    }
+
+    if let Err(e) = user_db::add_known_words(&words).await {
+        log::error!("Cannot import known words due to error {e}");
+        state.set(ImportState::Idle);
+        return;
+    }
+
+    known_words::load(&known).await;
+    state.set(ImportState::Done {
+        imported: words.len(),
+        unmatched,
+    });
+}
+
+#[derive(Props)]
+pub struct KnownImportProps<'a> {
+    known: &'a UseRef<HashSet<String>>,
+    db: &'a UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &'a UseState<yomi_dict::Reasons>,
+}
+
+pub fn known_import_component<'a>(cx: Scope<'a, KnownImportProps<'a>>) -> Element<'a> {
+    let known = cx.props.known;
+
+    // Text of the chosen file, which is parsed again if it turns out to have a header
+    let source = use_ref(cx, String::new);
+    let table = use_ref(cx, || None::<(Vec<String>, Vec<Vec<String>>)>);
+    let has_header = use_state(cx, || false);
+    let word_column = use_state(cx, || 0_usize);
+    // Index of the reading column plus one, so 0 means there is none
+    let reading_column = use_state(cx, || 0_usize);
+    let dictionary_only = use_state(cx, || true);
+    let state = use_ref(cx, || ImportState::Idle);
+
+    let known_count = known.read().len();
+
+    let status = match *state.read() {
+        ImportState::Idle => None,
+        ImportState::Importing(current, total) => Some(format!("Importing {current}/{total}")),
+        ImportState::Done {
+            imported,
+            unmatched,
+        } => Some(format!(
+            "Imported {imported} words. {unmatched} were not found in the dictionaries."
+        )),
+    };
+    let importing = matches!(*state.read(), ImportState::Importing(..));
+
+    let table_ref = table.read();
+    let options = table_ref.as_ref().map(|(columns, rows)| {
+        let row_count = rows.len();
+
+        rsx!(
+            p{ "{row_count} rows" }
+
+            table{
+                class: "text-sm my-1",
+
+                tbody{
+                    rows.iter().take(PREVIEW_ROWS).enumerate().map(|(i, row)| rsx!(tr{
+                        key: "{i}",
+                        row.iter().map(|field| rsx!(td{ class: "border px-1 truncate max-w-[8rem]", "{field}" }))
+                    }))
+                }
+            }
+
+            label{
+                class: "flex flex-col",
+
+                "Word"
+                select{
+                    class: "bg-gray-100 rounded p-1",
+
+                    value: "{word_column}",
+                    onchange: move |evt: FormEvent| word_column.set(evt.value.parse().unwrap_or_default()),
+
+                    columns.iter().enumerate().map(|(i, c)| rsx!(option{ key: "{i}", value: "{i}", "{c}" }))
+                }
+            }
+
+            label{
+                class: "flex flex-col",
+
+                "Reading"
+                select{
+                    class: "bg-gray-100 rounded p-1",
+
+                    value: "{reading_column}",
+                    onchange: move |evt: FormEvent| reading_column.set(evt.value.parse().unwrap_or_default()),
+
+                    option{ value: "0", "None" }
+                    columns.iter().enumerate().map(|(i, c)| {
+                        let value = i + 1;
+                        rsx!(option{ key: "{value}", value: "{value}", "{c}" })
+                    })
+                }
+            }
+
+            label{
+                class: "flex items-center gap-1",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{has_header}",
+                    onchange: move |evt: FormEvent| {
+                        let header = evt.value == "true";
+                        has_header.set(header);
+                        table.set(Some(parse(&source.read(), header)));
+                        word_column.set(0);
+                        reading_column.set(0);
+                    },
+                }
+
+                "First row names the columns"
+            }
+
+            label{
+                class: "flex items-center gap-1",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{dictionary_only}",
+                    onchange: move |evt: FormEvent| dictionary_only.set(evt.value == "true"),
+                }
+
+                "Skip words not in the dictionaries"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded disabled:opacity-50",
+                disabled: "{importing}",
+
+                onclick: move |_| {
+                    let Some((_, rows)) = table.read().clone() else { return };
+                    let options = ImportOptions {
+                        word_column: *word_column.get(),
+                        reading_column: reading_column.get().checked_sub(1),
+                        dictionary_only: *dictionary_only.get(),
+                    };
+
+                    wasm_bindgen_futures::spawn_local(import(
+                        rows,
+                        options,
+                        known.clone(),
+                        state.clone(),
+                        cx.props.db.clone(),
+                        cx.props.reasons.clone(),
+                    ));
+                },
+
+                "Import"
+            }
+        )
+    });
+
+    let source_moved = source.clone();
+    let table_moved = table.clone();
+    let has_header_moved = has_header.clone();
+    let state_moved = state.clone();
+    let word_column_moved = word_column.clone();
+    let reading_column_moved = reading_column.clone();
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-col gap-2 mt-2",
+
+            p{ "You know {known_count} words." }
+
+            p{
+                class: "text-sm text-gray-600",
+
+                "Import words from an Anki export, a CSV or TSV file, JSON or a list with one word per line."
+            }
+
+            upload_component{
+                label: "Choose file",
+                id: "known-words-upload",
+                upload_callback: move |data: Vec<u8>| {
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    let header = looks_like_header(&parse(&text, false).1);
+                    table_moved.set(Some(parse(&text, header)));
+                    has_header_moved.set(header);
+                    source_moved.set(text);
+                    word_column_moved.set(0);
+                    reading_column_moved.set(0);
+                    state_moved.set(ImportState::Idle);
+                },
+            }
+
+            options
+
+            status.map(|status| rsx!(p{ "{status}" }))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_csv() {
+        assert_eq!(split_csv("食べる,たべる"), ["食べる", "たべる"]);
+        assert_eq!(split_csv("a,,b"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn keeps_quoted_commas() {
+        assert_eq!(
+            split_csv("見る,\"to see, to look\",みる"),
+            ["見る", "to see, to look", "みる"]
+        );
+    }
+
+    #[test]
+    fn unescapes_quotes() {
+        assert_eq!(
+            split_csv("言う,\"to say \"\"hi\"\"\""),
+            ["言う", "to say \"hi\""]
+        );
+    }
+
+    #[test]
+    fn parses_anki_exports() {
+        let (columns, rows) = parse(
+            "#separator:tab\n#html:true\n食べる\tたべる\n見る\tみる\n",
+            false,
+        );
+        assert_eq!(columns, ["Column 1", "Column 2"]);
+        assert_eq!(rows, [["食べる", "たべる"], ["見る", "みる"]]);
+    }
+
+    #[test]
+    fn parses_word_lists() {
+        let (columns, rows) = parse("\u{feff}食べる\n\n 見る \n", false);
+        assert_eq!(columns, ["Column 1"]);
+        assert_eq!(rows, [["食べる"], ["見る"]]);
+    }
+
+    #[test]
+    fn parses_json() {
+        let (columns, rows) = parse(r#"[{"word": "食べる", "reading": "たべる"}]"#, false);
+        // Keys are sorted
+        assert_eq!(columns, ["reading", "word"]);
+        assert_eq!(rows, [["たべる", "食べる"]]);
+
+        let (_, rows) = parse(r#"[["見る", "みる"], "行く"]"#, false);
+        assert_eq!(rows, [vec!["見る", "みる"], vec!["行く"]]);
+    }
+
+    #[test]
+    fn skips_headers() {
+        let text = "Expression,Reading\n食べる,たべる\n";
+        let (_, rows) = parse(text, false);
+        assert!(looks_like_header(&rows));
+
+        let (columns, rows) = parse(text, true);
+        assert_eq!(columns, ["Expression", "Reading"]);
+        assert_eq!(rows, [["食べる", "たべる"]]);
+
+        assert!(!looks_like_header(
+            &parse("食べる,たべる\n見る,みる", false).1
+        ));
+    }
+
+    #[test]
+    fn cleans_fields() {
+        assert_eq!(clean("<b>食[た]べる</b>&nbsp;"), "食べる");
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{
+    highlight, scan,
+    segment::{self, Token},
+    user_db::{self, KnownWord},
+};
+
+/// Loads the expressions of all known words.
+pub async fn load(known: &UseRef<HashSet<String>>) {
+    match user_db::get_known_words().await {
+        Ok(words) => known.set(words.into_iter().map(|w| w.expression).collect()),
+        Err(e) => log::error!("Cannot load known words due to error {e}"),
+    }
+}
+
+/// Marks a word as known or no longer known.
+pub async fn set_known(
+    known: &UseRef<HashSet<String>>,
+    expression: String,
+    reading: String,
+    is_known: bool,
+) {
+    let result = if is_known {
+        let word = KnownWord {
+            expression: expression.clone(),
+            reading,
+            timestamp: js_sys::Date::now(),
+        };
+        user_db::add_known_words(&[word]).await
+    } else {
+        user_db::remove_known_word(&expression).await
+    };
+
+    if let Err(e) = result {
+        log::error!("Cannot update known word {expression} due to error {e}");
+        return;
+    }
+
+    known.with_mut(|known| {
+        if is_known {
+            known.insert(expression);
+        } else {
+            known.remove(&expression);
+        }
+    });
+}
+
+/// How many of the words in the current chapter are unknown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Density {
+    pub unknown: usize,
+    pub total: usize,
+}
+
+/// Splits the rendered chapter into words, once for each chapter.
+/// Gives up once `is_current` says the chapter changed meanwhile.
+pub async fn segment_chapter(
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+    is_current: impl Fn() -> bool,
+) -> Option<Vec<Token>> {
+    let text = scan::root_text(scan::READER_CONTENT_ID)?;
+
+    let tokens = match segment::segment(&text, reasons, db, |_, _| {}).await {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            log::error!("Cannot segment chapter due to error {e}");
+            return None;
+        }
+    };
+
+    is_current().then_some(tokens)
+}
+
+/// Underlines the words of the rendered chapter, as found by `segment_chapter`, that aren't known yet.
+pub fn highlight_unknown(known: &HashSet<String>, tokens: &[Token]) -> Density {
+    let spans = tokens
+        .iter()
+        .filter(|t| !known.contains(&t.expression))
+        .map(|t| (t.offset, t.len))
+        .collect::<Vec<_>>();
+    let ranges = scan::offset_ranges(scan::READER_CONTENT_ID, &spans);
+
+    highlight::set(highlight::UNKNOWN, &ranges);
+
+    Density {
+        unknown: ranges.len(),
+        total: tokens.len(),
+    }
+}
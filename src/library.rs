@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{
+    analysis::AnalysisState,
+    export,
+    read_state::ReaderState,
+    user_db::{self, BookProfile},
+};
+
+#[derive(Props)]
+pub struct LibraryProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+    analysis: &'a UseRef<AnalysisState>,
+    known: &'a UseRef<HashSet<String>>,
+}
+
+/// The analysed books, easiest first, so the next one can be picked at the right level.
+pub fn library_component<'a>(cx: Scope<'a, LibraryProps<'a>>) -> Element<'a> {
+    let current_book = cx
+        .props
+        .read_state
+        .with(|state| state.as_ref().map(ReaderState::get_title));
+
+    // Reload once an analysis finishes, since that saves a new profile
+    let analysed_book = cx.props.analysis.with(|a| match a {
+        AnalysisState::Done(result) => Some(result.book.clone()),
+        _ => None,
+    });
+    let profiles = use_future(cx, (&analysed_book,), |_| async move {
+        user_db::get_book_profiles().await
+    });
+
+    let profiles = match profiles.value() {
+        None => return cx.render(rsx!(p{"Loading library"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load library: {e}"}));
+        }
+        Some(Ok(profiles)) => profiles,
+    };
+
+    if profiles.is_empty() {
+        return cx.render(rsx!(p{
+            class: "mt-2",
+
+            "No books analysed yet. Analyze a book in the vocabulary panel to see how hard it is."
+        }));
+    }
+
+    // The stored profile of the open book may predate words marked known since
+    let mut profiles = profiles.clone();
+    if let AnalysisState::Done(result) = &*cx.props.analysis.read() {
+        let known = cx.props.known.read();
+        for profile in profiles.iter_mut().filter(|p| p.book == result.book) {
+            profile.difficulty = result.book_difficulty(&known);
+        }
+    }
+    profiles.sort_by(|a, b| {
+        b.difficulty
+            .known_coverage
+            .total_cmp(&a.difficulty.known_coverage)
+    });
+
+    cx.render(rsx! {
+        ul{
+            class: "list-none mt-2",
+
+            profiles.into_iter().map(|profile: BookProfile| {
+                let open = if Some(&profile.book) == current_book.as_ref() { " (open)" } else { "" };
+                let summary = profile.difficulty.summary();
+                let analysed = export::format_timestamp(profile.timestamp);
+
+                rsx!(li{
+                    key: "{profile.book}",
+                    class: "border-b py-1",
+
+                    h2{
+                        class: "text-lg font-medium",
+
+                        "{profile.book}{open}"
+                    }
+
+                    p{ "{summary}" }
+                    p{
+                        class: "text-sm text-gray-600",
+
+                        "{profile.chapters} chapters, {profile.characters} characters, analysed {analysed}"
+                    }
+                })
+            })
+        }
+    })
+}
@@ -1,12 +1,41 @@
 #![allow(clippy::future_not_send)]
 
+mod analysis;
+mod annotations;
+mod book_search;
+mod book_text;
+mod bookmarks;
+mod cfi;
+mod contents;
 mod definitions;
+mod deinflection;
+mod dict_index;
+mod difficulty;
+mod export;
+mod highlight;
+mod history;
 mod info_state;
+mod kana;
+mod known_import;
+mod known_words;
+mod library;
+mod mining;
 mod nav;
+mod normalize;
+mod notes_export;
 mod read_state;
 mod reader;
+mod reading_goals;
+mod reading_stats;
+mod scan;
+mod search;
+mod segment;
+mod sentences;
+mod settings;
 mod upload_component;
+mod user_db;
 mod view;
+mod vocabulary;
 
 extern crate web_sys;
 
@@ -15,6 +44,7 @@ use std::io::Cursor;
 use dioxus::prelude::*;
 use info_state::InfoState;
 use read_state::ReaderState;
+use settings::Settings;
 use yomi_dict::DB;
 
 fn main() {
@@ -54,6 +84,7 @@ async fn load_doc(read_state: &UseRef<Option<ReaderState>>) {
 async fn import_dict(
     db: &UseRef<Option<yomi_dict::IndexedDB>>,
     info_state: &UseRef<InfoState>,
+    index_status: &UseState<Option<dict_index::IndexStatus>>,
     data: Vec<u8>,
 ) {
     log::info!("Loading dictionary");
@@ -106,11 +137,57 @@ async fn import_dict(
             };
         }
 
+        build_index(info_state, index_status, &data).await;
+
         info_state.with_mut(|s| *s = InfoState::Idle);
         log::info!("Loaded dictionary");
     }
 }
 
+/// Indexes the terms for searches the dictionary database doesn't support.
+async fn build_index(
+    info_state: &UseRef<InfoState>,
+    index_status: &UseState<Option<dict_index::IndexStatus>>,
+    data: &[u8],
+) {
+    let index = dict_index::index_dict(data, |progress, total| {
+        info_state.with_mut(|s| {
+            *s = InfoState::LoadDict(info_state::LoadDictState::BuildingIndex(progress, total));
+        });
+    })
+    .await;
+
+    if let Err(err) = index {
+        log::error!("Failed to index dictionary with error {:?}", err);
+    }
+
+    // Earlier lookups may now have more results
+    definitions::clear_cache();
+
+    load_index_status(index_status).await;
+}
+
+async fn load_index_status(index_status: &UseState<Option<dict_index::IndexStatus>>) {
+    match dict_index::index_status().await {
+        Ok(status) => index_status.set(Some(status)),
+        Err(err) => log::error!("Failed to check the dictionary index with error {:?}", err),
+    }
+}
+
+/// Indexes a dictionary that is already imported, but missing from the indices.
+async fn reindex_dict(
+    info_state: &UseRef<InfoState>,
+    index_status: &UseState<Option<dict_index::IndexStatus>>,
+    data: Vec<u8>,
+) {
+    log::info!("Indexing dictionary");
+
+    build_index(info_state, index_status, &data).await;
+
+    info_state.with_mut(|s| *s = InfoState::Idle);
+    log::info!("Indexed dictionary");
+}
+
 async fn import_doc(data: Vec<u8>, read_state: &UseRef<Option<ReaderState>>) {
     log::info!("Loading document");
 
@@ -128,23 +205,66 @@ fn app(cx: Scope) -> Element {
 
     let dict_db = use_ref(cx, || None);
     let info_state = use_ref(cx, || InfoState::LoadDB);
+    let settings = use_ref(cx, Settings::load);
 
     let read_state = use_ref(cx, || None);
     let read_state_tomove = read_state.clone();
+    let index_status = use_state(cx, || None);
 
     // Cannot use async init for use_ref directly, so load database at next opportunity
     let loading = use_future(cx, (), |()| {
         let db_tomove = dict_db.clone();
         let info_state_tomove = info_state.clone();
+        let index_status = index_status.clone();
         async move {
             load_db(&db_tomove, &info_state_tomove).await;
             load_doc(&read_state_tomove).await;
+            load_index_status(&index_status).await;
         }
     });
 
     let db_tomove = dict_db.clone();
     let read_state_tomove = read_state.clone();
     let info_state_tomove = info_state.clone();
+    let index_status_tomove = index_status.clone();
+
+    // Dictionaries imported before their terms were indexed, or indexed by an older version, can be indexed again
+    let reindex = match index_status.get() {
+        Some(dict_index::IndexStatus::Empty) => {
+            Some("Upload your dictionaries again to enable searching them")
+        }
+        Some(dict_index::IndexStatus::Outdated) => {
+            Some("The search index is outdated. Upload all your dictionaries again to rebuild it")
+        }
+        Some(dict_index::IndexStatus::Current) | None => None,
+    };
+    let reindex = reindex.map(|message| {
+        let info_state = info_state.clone();
+        let index_status = index_status.clone();
+        rsx! {
+            li{
+                class: "mx-auto flex items-center",
+
+                span{
+                    class: "text-sm text-gray-600",
+
+                    "{message}"
+                }
+
+                upload_component::upload_component{
+                    id: "reindex_id",
+                    label: "Index Dict",
+                    upload_callback: move |data| {
+                        let info_state = info_state.clone();
+                        let index_status = index_status.clone();
+                        wasm_bindgen_futures::spawn_local(async move{
+                            reindex_dict(&info_state, &index_status, data).await;
+                        });
+                    }
+                }
+            }
+        }
+    });
 
     let tile = read_state
         .with(|s| {
@@ -188,13 +308,16 @@ fn app(cx: Scope) -> Element {
                                 upload_callback: move |data|{
                                     let db_tomove = db_tomove.clone();
                                     let info_state_tomove = info_state_tomove.clone();
+                                    let index_status_tomove = index_status_tomove.clone();
                                     wasm_bindgen_futures::spawn_local(async move{
-                                        import_dict(&db_tomove, &info_state_tomove, data).await;
+                                        import_dict(&db_tomove, &info_state_tomove, &index_status_tomove, data).await;
                                     });
                                 }
                             }
                         },
 
+                        reindex
+
                         li{
                             class: "mx-auto",
 
@@ -209,6 +332,18 @@ fn app(cx: Scope) -> Element {
                                     });
                                 }
                             }
+                        },
+
+                        li{
+                            class: "mx-auto",
+
+                            reading_goals::goal_progress_component{}
+                        }
+
+                        li{
+                            class: "mx-auto",
+
+                            settings::settings_component{ settings: settings }
                         }
                     }
                 }
@@ -217,7 +352,7 @@ fn app(cx: Scope) -> Element {
 
                     onscroll: |_| log::info!("scroll"),
 
-                    crate::reader::reader_component{ read_state: read_state, db: dict_db, reasons: reasons, info_state: info_state }
+                    crate::reader::reader_component{ read_state: read_state, db: dict_db, reasons: reasons, info_state: info_state, settings: settings }
                 }
             }
         }
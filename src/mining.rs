@@ -0,0 +1,160 @@
+use dioxus::prelude::*;
+
+use crate::{
+    definitions::get_terms,
+    export,
+    user_db::{self, MiningEntry},
+};
+
+/// Adds a word to the mining list, with the glossary from the dictionaries.
+pub async fn mine_word(
+    expression: String,
+    reading: String,
+    sentence: String,
+    book: String,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+) {
+    let glossary = match get_terms(&expression, reasons, db).await {
+        Ok(entries) => entries
+            .iter()
+            .filter(|d| d.expression == expression)
+            .flat_map(|d| {
+                d.entries
+                    .iter()
+                    .flat_map(|e| e.term.glossary.iter().cloned())
+            })
+            .collect(),
+        Err(e) => {
+            log::error!("Cannot get glossary of {expression} due to error {e}");
+            Vec::new()
+        }
+    };
+
+    let entry = MiningEntry {
+        id: None,
+        expression,
+        reading,
+        glossary,
+        sentence,
+        book,
+        timestamp: js_sys::Date::now(),
+    };
+
+    if let Err(e) = user_db::add_mining(&entry).await {
+        log::error!("Cannot add {} to mining due to error {e}", entry.expression);
+    }
+}
+
+fn export_mining(entries: &[MiningEntry]) {
+    // Without a header, so the file can be imported into Anki as is
+    let rows = entries.iter().map(|e| {
+        vec![
+            e.expression.clone(),
+            e.reading.clone(),
+            e.glossary.join("; "),
+            e.sentence.clone(),
+            e.book.clone(),
+        ]
+    });
+
+    export::download(
+        "mining.tsv",
+        "text/tab-separated-values",
+        &export::tsv(rows),
+    );
+}
+
+pub fn mining_component(cx: Scope) -> Element {
+    // Bumped to reload the list after removing entries
+    let revision = use_state(cx, || 0_u32);
+
+    let mining = use_future(cx, (revision.get(),), |_| async move {
+        user_db::get_mining().await
+    });
+
+    let mining = match mining.value() {
+        None => return cx.render(rsx!(p{"Loading mined words"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load mined words: {e}"}));
+        }
+        Some(Ok(mining)) => mining,
+    };
+
+    if mining.is_empty() {
+        return cx.render(rsx!(p{
+            class: "mt-2",
+
+            "Nothing mined yet. Send words here from the history or the vocabulary of a book to study them later."
+        }));
+    }
+
+    let count = mining.len();
+
+    cx.render(rsx! {
+        div{
+            class: "flex items-center gap-2 mt-2",
+
+            p{
+                class: "flex-1 text-sm text-gray-600",
+
+                "{count} words"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_mining(mining),
+
+                "Export for Anki"
+            }
+        }
+
+        ul{
+            class: "list-none",
+
+            mining.iter().map(|entry| {
+                let id = entry.id.unwrap_or_default();
+                let glossary = entry.glossary.join("; ");
+
+                rsx!(li{
+                    key: "{id}",
+                    class: "border-b py-1",
+
+                    h2{
+                        class: "text-lg font-medium inline-block mr-2",
+
+                        ruby {
+                            "{entry.expression}"
+                            rt{ "{entry.reading}" }
+                        }
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                        onclick: move |_| {
+                            let revision = revision.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                if let Err(e) = user_db::delete_mining(id).await {
+                                    log::error!("Cannot remove mined word due to error {e}");
+                                }
+                                revision.modify(|r| r.wrapping_add(1));
+                            });
+                        },
+
+                        "Remove"
+                    }
+
+                    p{ "{glossary}" }
+                    p{
+                        class: "text-sm text-gray-600",
+
+                        "{entry.sentence}"
+                    }
+                })
+            })
+        }
+    })
+}
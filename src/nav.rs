@@ -1,20 +1,70 @@
 use dioxus::prelude::*;
 
-use crate::read_state::ReaderState;
+use crate::{
+    read_state::{Progress, ReaderState},
+    reading_stats::format_duration,
+};
+
+/// Estimated time left in the chapter and the book at `speed` characters per hour.
+#[allow(clippy::cast_precision_loss)]
+fn time_left(progress: &Progress, speed: f64) -> String {
+    let duration = |chars: usize| format_duration(chars as f64 / speed * 60.0 * 60.0 * 1000.0);
+    let chapter = duration(progress.chapter_left);
+    let book = duration(progress.total.saturating_sub(progress.read));
+
+    format!(", {chapter} left in chapter, {book} left in book")
+}
 
 #[derive(Props)]
 pub struct NavProps<'a> {
     read_state: &'a UseRef<Option<ReaderState>>,
+    /// Progress through the book, or None until the chapters were counted
+    progress: &'a UseState<Option<Progress>>,
+    /// Characters read per hour in the book, if enough was read to tell
+    speed: &'a UseFuture<Option<f64>>,
 }
 
 pub fn nav_component<'a>(cx: Scope<'a, NavProps<'a>>) -> Element<'a> {
     let read_state = cx.props.read_state;
 
+    let speed = cx.props.speed;
+
     let (current_page, page_count) = match read_state.read().as_ref() {
-        Some(state) => (state.get_page(), state.get_page_count()),
+        Some(state) => (state.get_page() + 1, state.get_page_count()),
         _ => return cx.render(rsx! {p{"No document"}}),
     };
 
+    let progress_bar = cx.props.progress.get().as_ref().map(|progress| {
+        let percent = format!("{:.1}", progress.percent());
+        let time_left = speed
+            .value()
+            .copied()
+            .flatten()
+            .filter(|&speed| speed > 0.0)
+            .map_or_else(String::new, |speed| time_left(progress, speed));
+
+        rsx! {
+            div{
+                class: "my-1",
+
+                div{
+                    class: "w-full h-1 bg-gray-200 rounded-full",
+
+                    div{
+                        class: "h-1 bg-blue-500 rounded-full",
+                        style: "width: {percent}%",
+                    }
+                }
+
+                p{
+                    class: "text-sm text-gray-600 text-center",
+
+                    "{percent}% of the book{time_left}"
+                }
+            }
+        }
+    });
+
     cx.render(rsx! {
         nav{
             class: "flex",
@@ -41,5 +91,7 @@ pub fn nav_component<'a>(cx: Scope<'a, NavProps<'a>>) -> Element<'a> {
                 "Next"
             }
         }
+
+        progress_bar
     })
 }
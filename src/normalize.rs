@@ -0,0 +1,393 @@
+use crate::kana;
+
+/// Which kinds of text preprocessing are applied before looking up scanned text.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Normalization {
+    /// Half-width katakana and full-width or half-width alphanumerics
+    pub width: bool,
+    /// Katakana spellings of hiragana words and the other way around
+    pub kana: bool,
+    /// Iteration marks like 々 and ゝ
+    pub iteration_marks: bool,
+    /// Long vowel marks, which are expanded to the vowel or left out
+    pub long_vowels: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            width: true,
+            kana: true,
+            iteration_marks: true,
+            long_vowels: true,
+        }
+    }
+}
+
+// Too many variants make every lookup slow, so each step may only add this many.
+// Budgets per step keep later steps from being crowded out by earlier ones.
+const MAX_STEP_VARIANTS: usize = 2;
+
+const HALF_WIDTH_KATAKANA: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ";
+const FULL_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+// Kana whose voiced form directly follows them
+const VOICEABLE: &str =
+    "かきくけこさしすせそたちつてとはひふへほカキクケコサシスセソタチツテトハヒフヘホ";
+const SEMI_VOICEABLE: &str = "はひふへほハヒフヘホ";
+
+const VOWELS: [(char, &str); 5] = [
+    ('あ', "あかさたなはまやらわがざだばぱぁゃゎ"),
+    ('い', "いきしちにひみりぎじぢびぴぃ"),
+    ('う', "うくすつぬふむゆるぐずづぶぷぅゅゔ"),
+    ('え', "えけせてねへめれげぜでべぺぇ"),
+    ('お', "おこそとのほもよろをごぞどぼぽぉょ"),
+];
+
+// Offset between full-width and ASCII forms
+const FULL_WIDTH_OFFSET: u32 = 0xfee0;
+
+/// A preprocessed form of scanned text.
+pub struct Variant {
+    pub text: String,
+    /// For every char of the variant, how many chars of the original text it covers up to and including itself
+    ends: Vec<usize>,
+}
+
+impl Variant {
+    fn original(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ends: (1..=text.chars().count()).collect(),
+        }
+    }
+
+    /// Maps the length of a match in the variant to its length in the original text.
+    pub fn source_len(&self, len: usize) -> usize {
+        match len.min(self.ends.len()) {
+            0 => 0,
+            len => self.ends[len - 1],
+        }
+    }
+
+    fn transform(&self, f: fn(&[char]) -> Output) -> Self {
+        let chars = self.text.chars().collect::<Vec<_>>();
+        let output = f(&chars);
+
+        Self {
+            text: output.chars.into_iter().collect(),
+            ends: output
+                .ends
+                .into_iter()
+                .map(|end| self.source_len(end))
+                .collect(),
+        }
+    }
+}
+
+/// Transformed chars with the number of input chars consumed by each of them.
+#[derive(Default)]
+struct Output {
+    chars: Vec<char>,
+    ends: Vec<usize>,
+}
+
+impl Output {
+    fn push(&mut self, c: char, end: usize) {
+        self.chars.push(c);
+        self.ends.push(end);
+    }
+
+    fn last(&self) -> Option<char> {
+        self.chars.last().copied()
+    }
+
+    /// Replaces the last char, which now also covers the input up to `end`.
+    fn replace_last(&mut self, c: char, end: usize) {
+        if let (Some(last), Some(last_end)) = (self.chars.last_mut(), self.ends.last_mut()) {
+            *last = c;
+            *last_end = end;
+        }
+    }
+}
+
+fn shift(c: char, offset: u32) -> char {
+    char::from_u32(u32::from(c) + offset).unwrap_or(c)
+}
+
+fn map_each(chars: &[char], f: impl Fn(char) -> char) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        output.push(f(c), i + 1);
+    }
+
+    output
+}
+
+fn widen_katakana(chars: &[char], output: &mut Output, i: usize) -> bool {
+    let c = chars[i];
+    if let Some(index) = HALF_WIDTH_KATAKANA.chars().position(|h| h == c) {
+        output.push(FULL_WIDTH_KATAKANA.chars().nth(index).unwrap_or(c), i + 1);
+        return true;
+    }
+
+    // Voiced sound marks are separate chars in half-width katakana
+    let mark = match c {
+        'ﾞ' => Some(1),
+        'ﾟ' => Some(2),
+        _ => None,
+    };
+    let Some(offset) = mark else { return false };
+
+    match output.last() {
+        Some('ウ') if offset == 1 => output.replace_last('ヴ', i + 1),
+        Some(last) if offset == 1 && VOICEABLE.contains(last) => {
+            output.replace_last(shift(last, 1), i + 1);
+        }
+        Some(last) if offset == 2 && SEMI_VOICEABLE.contains(last) => {
+            output.replace_last(shift(last, 2), i + 1);
+        }
+        _ => output.push(if offset == 1 { '゛' } else { '゜' }, i + 1),
+    }
+
+    true
+}
+
+/// Widens half-width katakana and narrows full-width alphanumerics.
+fn to_half_width(chars: &[char]) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        if widen_katakana(chars, &mut output, i) {
+            continue;
+        }
+
+        let c = match c {
+            '\u{ff01}'..='\u{ff5e}' => {
+                char::from_u32(u32::from(c) - FULL_WIDTH_OFFSET).unwrap_or(c)
+            }
+            '\u{3000}' => ' ',
+            _ => c,
+        };
+        output.push(c, i + 1);
+    }
+
+    output
+}
+
+/// Widens half-width katakana and ASCII alphanumerics, as dictionaries often spell them.
+fn to_full_width(chars: &[char]) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        if widen_katakana(chars, &mut output, i) {
+            continue;
+        }
+
+        let c = if c.is_ascii_graphic() {
+            shift(c, FULL_WIDTH_OFFSET)
+        } else {
+            c
+        };
+        output.push(c, i + 1);
+    }
+
+    output
+}
+
+fn to_hiragana(chars: &[char]) -> Output {
+    map_each(chars, |c| {
+        kana::katakana_to_hiragana(&c.to_string())
+            .chars()
+            .next()
+            .unwrap_or(c)
+    })
+}
+
+fn to_katakana(chars: &[char]) -> Output {
+    map_each(chars, |c| {
+        kana::hiragana_to_katakana(&c.to_string())
+            .chars()
+            .next()
+            .unwrap_or(c)
+    })
+}
+
+/// Repeats the char before iteration marks, voicing it for ゞ and ヾ.
+fn expand_iteration_marks(chars: &[char]) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        let repeated = match (c, output.last()) {
+            ('々' | 'ゝ' | 'ヽ', Some(last)) => last,
+            ('ゞ' | 'ヾ', Some(last)) if VOICEABLE.contains(last) => shift(last, 1),
+            ('ゞ' | 'ヾ', Some(last)) => last,
+            _ => c,
+        };
+        output.push(repeated, i + 1);
+    }
+
+    output
+}
+
+fn vowel_of(c: char) -> Option<char> {
+    let hiragana = kana::katakana_to_hiragana(&c.to_string());
+    let vowel = VOWELS
+        .iter()
+        .find(|(_, kana)| kana.contains(hiragana.as_str()))
+        .map(|(vowel, _)| *vowel)?;
+
+    // Keep the script of the kana that is lengthened
+    if hiragana.starts_with(c) {
+        Some(vowel)
+    } else {
+        kana::hiragana_to_katakana(&vowel.to_string())
+            .chars()
+            .next()
+    }
+}
+
+/// Replaces long vowel marks after kana with the vowel they lengthen, eg すごーい to すごおい.
+fn expand_long_vowels(chars: &[char]) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        let vowel = (c == 'ー')
+            .then(|| output.last().and_then(vowel_of))
+            .flatten();
+        output.push(vowel.unwrap_or(c), i + 1);
+    }
+
+    output
+}
+
+/// Leaves out long vowel marks after kana, eg すごーい to すごい.
+fn remove_long_vowels(chars: &[char]) -> Output {
+    let mut output = Output::default();
+    for (i, &c) in chars.iter().enumerate() {
+        let lengthens = c == 'ー' && output.last().and_then(vowel_of).is_some();
+
+        // A dropped mark is covered by the char after it
+        if !lengthens {
+            output.push(c, i + 1);
+        }
+    }
+
+    output
+}
+
+/// Generates the forms of `text` to look up, starting with the text itself.
+pub fn variants(text: &str, normalization: Normalization) -> Vec<Variant> {
+    let steps: [(bool, &[fn(&[char]) -> Output]); 4] = [
+        (normalization.width, &[to_half_width, to_full_width]),
+        (normalization.iteration_marks, &[expand_iteration_marks]),
+        (
+            normalization.long_vowels,
+            &[expand_long_vowels, remove_long_vowels],
+        ),
+        (normalization.kana, &[to_hiragana, to_katakana]),
+    ];
+
+    let mut variants = vec![Variant::original(text)];
+    for (_, transforms) in steps.into_iter().filter(|(enabled, _)| *enabled) {
+        // Variants closest to the original text come first, so they are kept
+        let mut added = Vec::new();
+        'step: for variant in &variants {
+            for transform in transforms {
+                let transformed = variant.transform(*transform);
+                let known = variants
+                    .iter()
+                    .chain(&added)
+                    .any(|v: &Variant| v.text == transformed.text);
+                if !known {
+                    added.push(transformed);
+                    if added.len() == MAX_STEP_VARIANTS {
+                        break 'step;
+                    }
+                }
+            }
+        }
+
+        variants.extend(added);
+    }
+
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(text: &str, normalization: Normalization) -> Vec<String> {
+        variants(text, normalization)
+            .into_iter()
+            .map(|v| v.text)
+            .collect()
+    }
+
+    const NONE: Normalization = Normalization {
+        width: false,
+        kana: false,
+        iteration_marks: false,
+        long_vowels: false,
+    };
+
+    #[test]
+    fn starts_with_the_original() {
+        assert_eq!(texts("食べる", Normalization::default())[0], "食べる");
+        assert_eq!(texts("食べる", NONE), ["食べる"]);
+    }
+
+    #[test]
+    fn converts_width() {
+        let width = Normalization {
+            width: true,
+            ..NONE
+        };
+        assert!(texts("ｺｰﾋｰ", width).contains(&"コーヒー".to_string()));
+        assert!(texts("ﾊﾟﾝ", width).contains(&"パン".to_string()));
+        assert!(texts("ＡＢＣ", width).contains(&"ABC".to_string()));
+    }
+
+    #[test]
+    fn expands_iteration_marks() {
+        let marks = Normalization {
+            iteration_marks: true,
+            ..NONE
+        };
+        assert_eq!(texts("時々", marks), ["時々", "時時"]);
+    }
+
+    #[test]
+    fn handles_long_vowels() {
+        let vowels = Normalization {
+            long_vowels: true,
+            ..NONE
+        };
+        assert_eq!(
+            texts("すごーい", vowels),
+            ["すごーい", "すごおい", "すごい"]
+        );
+    }
+
+    #[test]
+    fn converts_kana() {
+        let kana = Normalization { kana: true, ..NONE };
+        assert_eq!(texts("ひらがな", kana), ["ひらがな", "ヒラガナ"]);
+    }
+
+    #[test]
+    fn keeps_kana_variants_after_other_steps() {
+        // Each step has its own budget, so earlier steps leave room for the kana variants
+        let all = texts("ｺｰﾋｰ", Normalization::default());
+        assert!(all.contains(&"こーひー".to_string()));
+        assert!(all.len() <= 1 + 4 * MAX_STEP_VARIANTS);
+    }
+
+    #[test]
+    fn maps_lengths_to_the_original() {
+        let variants = variants("ｶﾞｰﾃﾞﾝ", Normalization::default());
+        let full = variants.iter().find(|v| v.text == "ガーデン").unwrap();
+
+        // ガ is written with two half-width chars
+        assert_eq!(full.source_len(1), 2);
+        assert_eq!(full.source_len(4), 6);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    export,
+    user_db::{self, Annotation, Bookmark, UserDbError},
+};
+
+// Bumped if the format changes, so older backups can still be read
+const FORMAT_VERSION: u32 = 1;
+
+/// The bookmarks and annotations of a book, as backed up to JSON.
+#[derive(Serialize, Deserialize)]
+pub struct BookNotes {
+    pub version: u32,
+    pub book: String,
+    pub bookmarks: Vec<Bookmark>,
+    pub annotations: Vec<Annotation>,
+}
+
+pub async fn load(book: &str) -> Result<BookNotes, UserDbError> {
+    Ok(BookNotes {
+        version: FORMAT_VERSION,
+        book: book.to_string(),
+        bookmarks: user_db::get_bookmarks(book).await?,
+        annotations: user_db::get_annotations(book).await?,
+    })
+}
+
+/// Quotes every line of `text`, so paragraphs stay inside the quote.
+fn quote(text: &str) -> String {
+    text.trim()
+        .lines()
+        .map(|line| format!("> {line}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats the notes as Markdown, grouped by chapter in reading order.
+/// `chapter_titles` are the titles of the book's chapters by spine index.
+pub fn markdown(notes: &BookNotes, chapter_titles: &[String]) -> String {
+    markdown_with_dates(notes, chapter_titles, export::format_timestamp)
+}
+
+/// Like `markdown`, with timestamps formatted by `format_date`.
+fn markdown_with_dates(
+    notes: &BookNotes,
+    chapter_titles: &[String],
+    format_date: impl Fn(f64) -> String,
+) -> String {
+    enum Item<'a> {
+        Bookmark(&'a Bookmark),
+        Annotation(&'a Annotation),
+    }
+
+    let mut items = notes
+        .bookmarks
+        .iter()
+        .map(|b| (b.chapter, b.offset, Item::Bookmark(b)))
+        .chain(
+            notes
+                .annotations
+                .iter()
+                .map(|a| (a.chapter, a.offset, Item::Annotation(a))),
+        )
+        .collect::<Vec<_>>();
+    items.sort_by_key(|&(chapter, offset, _)| (chapter, offset));
+
+    let mut text = format!("# {}\n", notes.book);
+    let mut current_chapter = None;
+
+    for (chapter, _, item) in items {
+        if current_chapter != Some(chapter) {
+            current_chapter = Some(chapter);
+            let title = chapter_titles
+                .get(chapter)
+                .cloned()
+                .unwrap_or_else(|| format!("Chapter {}", chapter + 1));
+            text.push_str(&format!("\n## {title}\n"));
+        }
+
+        match item {
+            Item::Bookmark(b) => {
+                let date = format_date(b.timestamp);
+                text.push_str(&format!(
+                    "\n**Bookmark: {}**, {date}\n\n{}\n",
+                    b.name,
+                    quote(&b.text)
+                ));
+                if !b.note.is_empty() {
+                    text.push_str(&format!("\n{}\n", b.note));
+                }
+            }
+            Item::Annotation(a) => {
+                let date = format_date(a.timestamp);
+                text.push_str(&format!("\n{}\n", quote(&a.text)));
+                if !a.note.is_empty() {
+                    text.push_str(&format!("\n{}\n", a.note));
+                }
+                text.push_str(&format!("\n*{}, {date}*\n", a.color.label()));
+            }
+        }
+    }
+
+    text
+}
+
+pub fn export_markdown(notes: &BookNotes, chapter_titles: &[String]) {
+    export::download(
+        &format!("{} notes.md", notes.book),
+        "text/markdown",
+        &markdown(notes, chapter_titles),
+    );
+}
+
+pub fn export_json(notes: &BookNotes) {
+    match serde_json::to_string_pretty(notes) {
+        Ok(json) => export::download(
+            &format!("{} notes.json", notes.book),
+            "application/json",
+            &json,
+        ),
+        Err(e) => log::error!("Cannot export notes due to error {e}"),
+    }
+}
+
+/// Adds the bookmarks and annotations of a JSON backup, skipping those that exist already.
+/// Returns the number of notes added.
+pub async fn import(data: &[u8]) -> Result<usize, NotesImportError> {
+    let notes: BookNotes = serde_json::from_slice(data)?;
+    if notes.version > FORMAT_VERSION {
+        return Err(NotesImportError::Version(notes.version));
+    }
+
+    // Backups are per book, but each note names its book, so trust that over the file
+    let mut added = 0;
+    let mut existing: Option<(String, BookNotes)> = None;
+
+    for bookmark in notes.bookmarks {
+        let current = existing_notes(&mut existing, &bookmark.book).await?;
+        let duplicate = current.bookmarks.iter().any(|b| {
+            (b.chapter, b.offset, &b.name) == (bookmark.chapter, bookmark.offset, &bookmark.name)
+        });
+        if !duplicate {
+            let bookmark = Bookmark {
+                id: None,
+                ..bookmark
+            };
+            user_db::add_bookmark(&bookmark).await?;
+            // The backup may hold the same note twice
+            current.bookmarks.push(bookmark);
+            added += 1;
+        }
+    }
+
+    for annotation in notes.annotations {
+        let current = existing_notes(&mut existing, &annotation.book).await?;
+        let duplicate = current.annotations.iter().any(|a| {
+            (a.chapter, a.offset, a.len, a.color)
+                == (
+                    annotation.chapter,
+                    annotation.offset,
+                    annotation.len,
+                    annotation.color,
+                )
+        });
+        if !duplicate {
+            let annotation = Annotation {
+                id: None,
+                ..annotation
+            };
+            user_db::add_annotation(&annotation).await?;
+            current.annotations.push(annotation);
+            added += 1;
+        }
+    }
+
+    Ok(added)
+}
+
+/// The stored notes of `book`, loaded once while importing and kept up to date with the added ones.
+async fn existing_notes<'a>(
+    cache: &'a mut Option<(String, BookNotes)>,
+    book: &str,
+) -> Result<&'a mut BookNotes, UserDbError> {
+    if cache.as_ref().map_or(true, |(cached, _)| cached != book) {
+        *cache = Some((book.to_string(), load(book).await?));
+    }
+
+    Ok(&mut cache.as_mut().expect("notes should be loaded").1)
+}
+
+#[derive(Error, Debug)]
+pub enum NotesImportError {
+    #[error("The file is not a notes backup: `{0}`")]
+    Json(#[from] serde_json::Error),
+    #[error("The backup was made by a newer version (format {0})")]
+    Version(u32),
+    #[error("Cannot save the notes: `{0}`")]
+    UserDb(#[from] UserDbError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_db::Color;
+
+    fn bookmark(chapter: usize, offset: usize, name: &str) -> Bookmark {
+        Bookmark {
+            id: None,
+            name: name.to_string(),
+            note: String::new(),
+            book: "本".to_string(),
+            chapter,
+            offset,
+            cfi: None,
+            text: "ある日".to_string(),
+            timestamp: 0.0,
+        }
+    }
+
+    fn annotation(chapter: usize, offset: usize, text: &str, note: &str) -> Annotation {
+        Annotation {
+            id: None,
+            book: "本".to_string(),
+            chapter,
+            offset,
+            len: text.chars().count(),
+            text: text.to_string(),
+            color: Color::Yellow,
+            note: note.to_string(),
+            timestamp: 0.0,
+        }
+    }
+
+    #[test]
+    fn groups_notes_by_chapter_in_reading_order() {
+        let notes = BookNotes {
+            version: FORMAT_VERSION,
+            book: "本".to_string(),
+            bookmarks: vec![bookmark(1, 5, "Later"), bookmark(0, 10, "Start")],
+            annotations: vec![
+                annotation(0, 2, "一行目\n二行目", "Note"),
+                annotation(1, 0, "終わり", ""),
+            ],
+        };
+        let titles = ["First".to_string()];
+
+        let text = markdown_with_dates(&notes, &titles, |_| "today".to_string());
+
+        assert_eq!(
+            text,
+            "# 本\n\
+             \n## First\n\
+             \n> 一行目\n> 二行目\n\nNote\n\n*Yellow, today*\n\
+             \n**Bookmark: Start**, today\n\n> ある日\n\
+             \n## Chapter 2\n\
+             \n> 終わり\n\n*Yellow, today*\n\
+             \n**Bookmark: Later**, today\n\n> ある日\n"
+        );
+    }
+}
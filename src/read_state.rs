@@ -7,13 +7,50 @@ use std::{
     path::{Path, PathBuf},
 };
 use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+use crate::{book_text, cfi::Cfi, scan};
+
+/// Where to scroll to once the chapter is rendered.
+pub enum PendingPosition {
+    /// A character offset, shown with some text before it for context
+    Offset(usize),
+    /// A position to put at the top, like where the reader left off
+    Cfi(Cfi),
+    /// Text found by a search, which is highlighted too
+    Match { offset: usize, len: usize },
+}
+
+/// How far the reader is through the book, in characters without markup or furigana.
+pub struct Progress {
+    pub read: usize,
+    pub total: usize,
+    /// Characters from the position to the end of the chapter
+    pub chapter_left: usize,
+}
+
+impl Progress {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.read as f64 * 100.0 / self.total as f64
+    }
+}
 
 pub struct ReaderState {
     doc: EpubDoc<Cursor<Vec<u8>>>,
     page: usize,
-    scroll_top: i32,
     text: Option<String>,
-    scroll_blocked: bool,
+    /// The text at the top of the reader when it was last saved
+    position: Option<Cfi>,
+    /// The same position as a character offset in the chapter
+    offset: usize,
+    pending: Option<PendingPosition>,
+    /// Characters in each chapter without markup or furigana, or None until counted
+    chapter_lengths: Vec<Option<usize>>,
 }
 
 // TODO error checking
@@ -26,16 +63,33 @@ fn save_page(page: usize) {
     storage.set_item("page", &page.to_string()).ok();
 }
 
-fn save_scroll(scroll_top: i32) {
+fn save_cfi(cfi: &Cfi) {
     let window = web_sys::window().expect("should have window");
     let storage = window
         .local_storage()
         .expect("should be able to get storage")
         .expect("should have storage");
-    storage.set_item("scroll_top", &scroll_top.to_string()).ok();
+    storage.set_item("cfi", &cfi.to_string()).ok();
+
+    // Keep the address pointing to the position, so it can be bookmarked or shared
+    if let Ok(history) = window.history() {
+        history
+            .replace_state_with_url(&JsValue::NULL, "", Some(&format!("#{cfi}")))
+            .ok();
+    }
 }
 
-fn apply_scroll(scroll_top: i32) {
+/// A position the address points to, eg from a shared link.
+fn location_cfi() -> Option<Cfi> {
+    let window = web_sys::window().expect("should have window");
+    let hash = window.location().hash().ok()?;
+    // Browsers escape characters like `^` in the address
+    let hash = js_sys::decode_uri_component(hash.strip_prefix('#')?).ok()?;
+
+    Cfi::parse(&String::from(hash))
+}
+
+fn scroll_to_top() {
     let window = web_sys::window().expect("should have window");
     let document = window.document().expect("should have document");
 
@@ -45,7 +99,7 @@ fn apply_scroll(scroll_top: i32) {
             log::warn!("Couldn't get element to set scroll position");
         },
         |element| {
-            element.set_scroll_top(scroll_top);
+            element.set_scroll_top(0);
         },
     );
 }
@@ -63,20 +117,34 @@ async fn get_doc_db() -> rexie::Result<Rexie> {
 }
 
 impl ReaderState {
-    fn new(mut doc: EpubDoc<Cursor<Vec<u8>>>, page: usize, scroll_top: i32) -> Self {
+    /// Opens `doc` at `position`, or at the start of the chapter `page` if there is none.
+    fn new(mut doc: EpubDoc<Cursor<Vec<u8>>>, page: usize, position: Option<Cfi>) -> Self {
         let text = doc.get_current_str().map(|(s, _)| s);
+        let chapter_lengths = vec![None; doc.get_num_pages()];
 
         save_page(page);
-        save_scroll(scroll_top);
-        apply_scroll(scroll_top);
+        scroll_to_top();
 
-        Self {
+        let mut state = Self {
             doc,
             page,
-            scroll_top,
             text,
-            scroll_blocked: false,
+            position: None,
+            offset: 0,
+            pending: None,
+            chapter_lengths,
+        };
+
+        match position {
+            Some(position) => {
+                save_cfi(&position);
+                state.pending = Some(PendingPosition::Cfi(position.clone()));
+                state.position = Some(position);
+            }
+            None => state.save_chapter_start(),
         }
+
+        state
     }
 
     pub(crate) fn get_title(&self) -> String {
@@ -105,8 +173,46 @@ impl ReaderState {
         self.doc.get_num_pages()
     }
 
-    pub(crate) fn set_scoll_blocked(&mut self, blocked: bool) {
-        self.scroll_blocked = blocked;
+    /// Id of the chapter `page` in the spine.
+    pub(crate) fn get_spine_id(&self, page: usize) -> Option<String> {
+        self.doc.spine.get(page).cloned()
+    }
+
+    /// The XHTML of the chapter `page`, without opening it.
+    pub(crate) fn get_chapter_str(&mut self, page: usize) -> Option<String> {
+        let id = self.doc.spine.get(page)?.clone();
+        self.doc.get_resource_str(&id).map(|(s, _)| s)
+    }
+
+    /// The title the table of contents gives the chapter `page`.
+    pub(crate) fn get_chapter_title(&self, page: usize) -> Option<String> {
+        let id = self.doc.spine.get(page)?;
+        let (path, _) = self.doc.resources.get(id)?;
+
+        // Entries may point to an anchor inside the chapter
+        self.doc
+            .toc
+            .iter()
+            .find(|point| {
+                let content = point.content.to_string_lossy();
+                let content = content.split('#').next().unwrap_or_default();
+                Path::new(content) == path
+            })
+            .map(|point| point.label.clone())
+    }
+
+    /// Saves the start of the new chapter, until the reader scrolls somewhere else in it.
+    fn save_chapter_start(&mut self) {
+        self.offset = 0;
+        self.position = self
+            .doc
+            .spine
+            .get(self.page)
+            .map(|idref| Cfi::chapter_start(self.page, idref));
+
+        if let Some(position) = &self.position {
+            save_cfi(position);
+        }
     }
 
     pub(crate) fn next_page(&mut self) {
@@ -114,8 +220,8 @@ impl ReaderState {
             self.page = self.doc.get_current_page();
             self.text = self.doc.get_current_str().map(|(s, _)| s);
             save_page(self.page);
-            self.set_scroll(0);
-            self.apply_scroll();
+            self.save_chapter_start();
+            scroll_to_top();
         }
     }
 
@@ -124,11 +230,145 @@ impl ReaderState {
             self.page = self.doc.get_current_page();
             self.text = self.doc.get_current_str().map(|(s, _)| s);
             save_page(self.page);
-            self.set_scroll(0);
-            self.apply_scroll();
+            self.save_chapter_start();
+            scroll_to_top();
+        }
+    }
+
+    fn open_page(&mut self, page: usize) -> bool {
+        if page != self.page {
+            if !self.doc.set_current_page(page) {
+                log::warn!("Cannot jump to chapter {page}");
+                return false;
+            }
+
+            self.page = self.doc.get_current_page();
+            self.text = self.doc.get_current_str().map(|(s, _)| s);
+            save_page(self.page);
+            self.save_chapter_start();
+            scroll_to_top();
+        }
+
+        true
+    }
+
+    /// Character offset of the position in the chapter, as last saved.
+    pub(crate) const fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Characters in the chapter `page` as the reader sees them, counted the first time they are needed.
+    pub(crate) fn chapter_length(&mut self, page: usize) -> Option<usize> {
+        if let Some(length) = *self.chapter_lengths.get(page)? {
+            return Some(length);
+        }
+
+        let length = self
+            .get_chapter_str(page)
+            .map_or(0, |html| book_text::plain_text(&html).chars().count());
+        self.chapter_lengths[page] = Some(length);
+
+        Some(length)
+    }
+
+    /// Counts the characters of the next chapter that wasn't counted yet.
+    /// Reading every chapter at once takes a while, so callers count them one at a time.
+    ///
+    /// Returns false once every chapter is counted.
+    pub(crate) fn count_next_chapter(&mut self) -> bool {
+        let Some(page) = self.chapter_lengths.iter().position(Option::is_none) else {
+            return false;
+        };
+        self.chapter_length(page);
+
+        true
+    }
+
+    /// Progress through the whole book up to the saved position, or None until every chapter is counted.
+    pub(crate) fn progress(&self) -> Option<Progress> {
+        let lengths = self
+            .chapter_lengths
+            .iter()
+            .copied()
+            .collect::<Option<Vec<_>>>()?;
+
+        let chapter = lengths.get(self.page).copied().unwrap_or_default();
+        let offset = self.offset.min(chapter);
+
+        Some(Progress {
+            read: lengths.iter().take(self.page).sum::<usize>() + offset,
+            total: lengths.iter().sum(),
+            chapter_left: chapter - offset,
+        })
+    }
+
+    /// Opens the chapter `page` and scrolls to the character `offset` in it once rendered.
+    pub(crate) fn jump_to(&mut self, page: usize, offset: Option<usize>) {
+        if self.open_page(page) {
+            self.pending = offset.map(PendingPosition::Offset);
+        }
+    }
+
+    /// Opens the chapter `page` and scrolls to and highlights the `len` characters at `offset` once rendered.
+    pub(crate) fn jump_to_match(&mut self, page: usize, offset: usize, len: usize) {
+        if self.open_page(page) {
+            self.pending = Some(PendingPosition::Match { offset, len });
+        }
+    }
+
+    /// Opens the chapter `cfi` points to and scrolls to the position once rendered.
+    /// Returns false if the book has no such chapter.
+    pub(crate) fn jump_to_cfi(&mut self, cfi: Cfi) -> bool {
+        let Some(page) = cfi.page(&self.doc.spine) else {
+            log::warn!("Cannot find the chapter of {cfi}");
+            return false;
+        };
+
+        if self.open_page(page) {
+            self.pending = Some(PendingPosition::Cfi(cfi));
+        }
+
+        true
+    }
+
+    pub(crate) const fn has_pending_position(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub(crate) fn take_pending_position(&mut self) -> Option<PendingPosition> {
+        self.pending.take()
+    }
+
+    /// Saves the text at the top of the reader as the position.
+    /// Unlike a scroll offset, this survives changes to the layout or images loading late.
+    pub(crate) fn save_position(&mut self) {
+        // Until then, the reader isn't where it is supposed to be
+        if self.pending.is_some() {
+            return;
+        }
+
+        let Some(offset) = scan::first_visible_offset(scan::READER_CONTENT_ID, "reader-scroll")
+        else {
+            return;
+        };
+        self.offset = offset;
+
+        let cfi = self
+            .doc
+            .spine
+            .get(self.page)
+            .and_then(|idref| Cfi::at_offset(self.page, idref, offset));
+        if let Some(cfi) = cfi {
+            save_cfi(&cfi);
+            self.position = Some(cfi);
         }
     }
 
+    /// Scrolls back to the saved position once rendered, eg after the layout changed.
+    pub(crate) fn restore_position(&mut self) {
+        self.pending = self.position.clone().map(PendingPosition::Cfi);
+    }
+
     pub(crate) async fn from_storage() -> Result<Option<Self>, ReadStateError> {
         let db = get_doc_db().await?;
 
@@ -136,11 +376,12 @@ impl ReaderState {
         let books = transaction.store("books")?;
 
         let Some(data) = books
-        .get_all(None, Some(1), None, None)
-        .await
-        .unwrap()
-        .first()
-        .map(|(_, v)| v.clone()) else{
+            .get_all(None, Some(1), None, None)
+            .await
+            .unwrap()
+            .first()
+            .map(|(_, v)| v.clone())
+        else {
             return Ok(None);
         };
 
@@ -159,14 +400,21 @@ impl ReaderState {
             .expect("Should be able to access storage")
             .map_or(Ok(0), |s| s.parse().map_err(|_| ReadStateError::Parse(s)))?;
 
-        let scroll_top = storage
-            .get_item("scroll_top")
-            .expect("Should be able to access storage")
-            .map_or(Ok(0), |s| s.parse().map_err(|_| ReadStateError::Parse(s)))?;
+        // A position from a shared link wins over the one saved
+        let cfi = location_cfi().or_else(|| {
+            storage
+                .get_item("cfi")
+                .expect("Should be able to access storage")
+                .and_then(|cfi| Cfi::parse(&cfi))
+        });
+        // A position in a chapter the book doesn't have is dropped for the saved chapter
+        let cfi_page = cfi.as_ref().and_then(|cfi| cfi.page(&doc.spine));
+        let cfi = cfi.filter(|_| cfi_page.is_some());
+        let page = cfi_page.unwrap_or(page);
 
         doc.set_current_page(page);
 
-        Ok(Some(Self::new(doc, page, scroll_top)))
+        Ok(Some(Self::new(doc, page, cfi)))
     }
 
     pub(crate) async fn from_bytes(data: Vec<u8>) -> Result<Self, ReadStateError> {
@@ -185,27 +433,7 @@ impl ReaderState {
 
         transaction.done().await?;
 
-        Ok(Self::new(doc, 0, 0))
-    }
-
-    // pub(crate) fn get_scroll(&self) -> i32 {
-    //     self.scroll_top
-    // }
-
-    pub(crate) fn set_scroll(&mut self, scroll_top: i32) {
-        if self.scroll_blocked {
-            self.scroll_blocked = false;
-            log::info!("Blocked scroll attempt");
-            self.apply_scroll();
-            return;
-        }
-
-        self.scroll_top = scroll_top;
-        save_scroll(scroll_top);
-    }
-
-    pub(crate) fn apply_scroll(&self) {
-        apply_scroll(self.scroll_top);
+        Ok(Self::new(doc, 0, None))
     }
 }
 
@@ -1,12 +1,23 @@
 extern crate web_sys;
 
+use std::{cell::Cell, collections::HashSet, rc::Rc};
+
 use dioxus::prelude::*;
 use wasm_bindgen::{prelude::Closure, JsCast};
 
 use crate::{
-    definitions::update_defs_and_selection,
+    analysis::AnalysisState,
+    annotations,
+    definitions::{push_lookup, update_defs_and_highlight, Lookup, LookupResult},
+    highlight, history,
     info_state::{InfoState, LoadDictState},
-    read_state::ReaderState,
+    known_words::{self, Density},
+    read_state::{Progress, ReaderState},
+    reading_stats,
+    scan::{Scan, SelectedText, TextPosition},
+    segment::Token,
+    settings::{Layout, Settings, MAX_SPLIT, MIN_SPLIT},
+    user_db::Color,
 };
 
 #[derive(Props)]
@@ -15,25 +26,328 @@ pub struct ReaderProps<'a> {
     db: &'a UseRef<Option<yomi_dict::IndexedDB>>,
     reasons: &'a UseState<yomi_dict::Reasons>,
     info_state: &'a UseRef<InfoState>,
+    settings: &'a UseRef<Settings>,
 }
 
-fn enable_scroll_callback(read_state: UseRef<Option<ReaderState>>) {
+const POPUP_WIDTH: f64 = 320.0;
+const POPUP_MAX_HEIGHT: f64 = 256.0;
+const POPUP_MARGIN: f64 = 4.0;
+
+/// Places the definitions popup next to the looked up word while keeping it on screen.
+fn popup_style(range: &web_sys::Range) -> Option<String> {
+    let window = web_sys::window().expect("should have window");
+    let width = window.inner_width().ok()?.as_f64()?;
+    let height = window.inner_height().ok()?.as_f64()?;
+
+    let word = range.get_bounding_client_rect();
+    let left = word.left().min(width - POPUP_WIDTH).max(0.0);
+
+    let space_below = height - word.bottom() - 2.0 * POPUP_MARGIN;
+    let space_above = word.top() - 2.0 * POPUP_MARGIN;
+
+    // Prefer showing the popup below the word, like a dropdown
+    let style = if space_below >= POPUP_MAX_HEIGHT || space_below >= space_above {
+        format!(
+            "left: {left}px; top: {}px; max-height: {}px;",
+            word.bottom() + POPUP_MARGIN,
+            space_below.min(POPUP_MAX_HEIGHT)
+        )
+    } else {
+        format!(
+            "left: {left}px; bottom: {}px; max-height: {}px;",
+            height - word.top() + POPUP_MARGIN,
+            space_above.min(POPUP_MAX_HEIGHT)
+        )
+    };
+
+    Some(style)
+}
+
+fn close_popup(popup: &UseRef<Option<String>>) {
+    if popup.read().is_some() {
+        popup.set(None);
+        highlight::clear(highlight::MATCH);
+    }
+}
+
+fn enable_escape_callback(popup: UseRef<Option<String>>) {
     let window = web_sys::window().expect("should have window");
-    let document = window.document().expect("should have document");
 
-    let Some(element) = document
-            .get_element_by_id("reader-scroll")
+    let escape_callback =
+        Closure::<dyn Fn(web_sys::KeyboardEvent)>::new(move |evt: web_sys::KeyboardEvent| {
+            if evt.key() == "Escape" {
+                close_popup(&popup);
+            }
+        });
+
+    window
+        .add_event_listener_with_callback("keydown", escape_callback.as_ref().unchecked_ref())
+        .unwrap();
+
+    escape_callback.forget();
+}
+
+// Matches Tailwind's `lg` breakpoint, from which the panes are shown side by side
+const WIDE_SCREEN: f64 = 1024.0;
+
+fn enable_resize_callbacks(
+    read_state: UseRef<Option<ReaderState>>,
+    settings: UseRef<Settings>,
+    resizing: UseRef<bool>,
+) {
+    let window = web_sys::window().expect("should have window");
+
+    let window_moved = window.clone();
+    let resizing_moved = resizing.clone();
+    let settings_moved = settings.clone();
+    let move_callback =
+        Closure::<dyn Fn(web_sys::PointerEvent)>::new(move |evt: web_sys::PointerEvent| {
+            if !*resizing_moved.read() {
+                return;
+            }
+
+            let Some(container) = window_moved
+                .document()
+                .and_then(|d| d.get_element_by_id("reader-split"))
             else {
                 return;
             };
+            let bounds = container.get_bounding_client_rect();
+
+            let wide = window_moved
+                .inner_width()
+                .ok()
+                .and_then(|w| w.as_f64())
+                .map_or(false, |w| w >= WIDE_SCREEN);
+
+            let split = if wide {
+                (f64::from(evt.client_x()) - bounds.left()) / bounds.width()
+            } else {
+                (f64::from(evt.client_y()) - bounds.top()) / bounds.height()
+            } * 100.0;
+
+            if split.is_finite() {
+                settings_moved.with_mut(|s| s.split = split.clamp(MIN_SPLIT, MAX_SPLIT));
+            }
+        });
+
+    let up_callback = Closure::<dyn Fn()>::new(move || {
+        if !*resizing.read() {
+            return;
+        }
+
+        resizing.set(false);
+        settings.read().save();
+
+        // The text moved while resizing, so go back to where the reader was
+        read_state.with_mut(|state| state.as_mut().map(ReaderState::restore_position));
+    });
+
+    window
+        .add_event_listener_with_callback("pointermove", move_callback.as_ref().unchecked_ref())
+        .unwrap();
+    window
+        .add_event_listener_with_callback("pointerup", up_callback.as_ref().unchecked_ref())
+        .unwrap();
+
+    move_callback.forget();
+    up_callback.forget();
+}
+
+/// What the panel next to the book shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Panel {
+    Definitions,
+    History,
+    Mining,
+    Known,
+    Vocabulary,
+    Sentences,
+    Contents,
+    Find,
+    Bookmarks,
+    Annotations,
+    Library,
+    Statistics,
+    Activity,
+}
+
+impl Panel {
+    const ALL: [Self; 13] = [
+        Self::Definitions,
+        Self::History,
+        Self::Mining,
+        Self::Known,
+        Self::Vocabulary,
+        Self::Sentences,
+        Self::Contents,
+        Self::Find,
+        Self::Bookmarks,
+        Self::Annotations,
+        Self::Library,
+        Self::Statistics,
+        Self::Activity,
+    ];
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Definitions => "Definitions",
+            Self::History => "History",
+            Self::Mining => "Mining",
+            Self::Known => "Known words",
+            Self::Vocabulary => "Vocabulary",
+            Self::Sentences => "Sentences",
+            Self::Contents => "Contents",
+            Self::Find => "Find in book",
+            Self::Bookmarks => "Bookmarks",
+            Self::Annotations => "Annotations",
+            Self::Library => "Library",
+            Self::Statistics => "Statistics",
+            Self::Activity => "Activity",
+        }
+    }
+}
+
+/// State updated by lookups started from the book.
+#[derive(Clone)]
+struct LookupTarget {
+    definitions: UseRef<Vec<Lookup>>,
+    db: UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: UseState<yomi_dict::Reasons>,
+    popup: UseRef<Option<String>>,
+    settings: UseRef<Settings>,
+    layout: Layout,
+    read_state: UseRef<Option<ReaderState>>,
+    panel: UseRef<Panel>,
+    /// Expression and offset of the last lookup put into the history
+    last_recorded: UseRef<Option<(String, Option<usize>)>>,
+}
+
+impl LookupTarget {
+    /// Looks up `scan` and shows the results, returning whether anything was found.
+    async fn lookup(&self, scan: &Scan) -> bool {
+        let normalization = self.settings.read().normalization;
+        let range = match update_defs_and_highlight(
+            &self.definitions,
+            &self.db,
+            self.reasons.get(),
+            normalization,
+            scan,
+        )
+        .await
+        {
+            // The newer lookup shows its own results, which mustn't be closed or recorded
+            LookupResult::Stale => return false,
+            LookupResult::Found(range) => Some(range),
+            LookupResult::NotFound => None,
+        };
+
+        match self.layout {
+            Layout::Popup => self.popup.set(range.as_ref().and_then(popup_style)),
+            Layout::Split if range.is_some() => {
+                if self.settings.read().definitions_collapsed {
+                    self.settings.with_mut(|s| {
+                        s.definitions_collapsed = false;
+                        s.save();
+                    });
+                    // The book pane shrinks, so stay where the reader was
+                    self.read_state
+                        .with_mut(|state| state.as_mut().map(ReaderState::restore_position));
+                }
+
+                if *self.panel.read() != Panel::Definitions {
+                    self.panel.set(Panel::Definitions);
+                }
+            }
+            Layout::Split => {}
+        }
+
+        range.is_some()
+    }
 
-    let element_moved = element.clone();
+    /// Adds the best match of the current lookup to the history.
+    async fn record(&self, scan: &Scan) {
+        let Some(expression) = self
+            .definitions
+            .with(|defs| Some(defs.last()?.entries.first()?.expression.clone()))
+        else {
+            return;
+        };
+
+        // Looking at the same word again shouldn't fill the history
+        let offset = scan.offset();
+        let key = Some((expression, offset));
+        if *self.last_recorded.read() == key {
+            return;
+        }
+        self.last_recorded.set(key);
+
+        history::record(
+            &self.definitions,
+            &self.read_state,
+            scan.text.clone(),
+            scan.sentence().unwrap_or_default(),
+            offset,
+        )
+        .await;
+    }
+}
+
+pub(crate) async fn sleep(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .expect("should have window")
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    });
+
+    wasm_bindgen_futures::JsFuture::from(promise).await.ok();
+}
+
+// Hovered words are only put into the history once they were shown this long
+const HOVER_RECORD_DELAY: i32 = 1000;
+
+// Saving the position looks at the layout, so it waits for scrolling to stop
+const SAVE_POSITION_DELAY: i32 = 300;
+
+fn enable_scroll_callback(
+    read_state: UseRef<Option<ReaderState>>,
+    resizing: UseRef<bool>,
+    progress: UseState<Option<Progress>>,
+) {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let Some(element) = document.get_element_by_id("reader-scroll") else {
+        return;
+    };
+
+    let save_generation = Rc::new(Cell::new(0_u32));
 
     let scroll_callback = Closure::<dyn Fn()>::new(move || {
-        let offset = element_moved.scroll_top();
-        if let Some(state) = read_state.write().as_mut() {
-            state.set_scroll(offset);
+        // The pane changing size moves the content around, but the reader didn't scroll
+        if *resizing.read() {
+            return;
         }
+
+        let generation = save_generation.get().wrapping_add(1);
+        save_generation.set(generation);
+
+        let read_state = read_state.clone();
+        let save_generation = save_generation.clone();
+        let progress = progress.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            sleep(SAVE_POSITION_DELAY).await;
+
+            if save_generation.get() == generation {
+                // Only the progress is shown differently, so the book doesn't need to render again
+                if let Some(state) = read_state.write_silent().as_mut() {
+                    state.save_position();
+                    reading_stats::record_position(state);
+                    progress.set(state.progress());
+                }
+            }
+        });
     });
 
     element
@@ -48,38 +362,330 @@ pub fn reader_component<'a>(cx: Scope<'a, ReaderProps<'a>>) -> Element<'a> {
     let info_state = cx.props.info_state;
     let db = cx.props.db;
     let reasons = cx.props.reasons;
+    let settings = cx.props.settings;
+
+    let definitions = use_ref(cx, Vec::<Lookup>::new);
+
+    // Hover lookups are debounced, so only the last one in a series is executed
+    let hover_generation = use_ref(cx, || 0_u32);
+    let hover_start = use_ref(cx, || None::<TextPosition>);
+
+    // Style positioning the popup while it is open
+    let popup = use_ref(cx, || None::<String>);
+
+    // Progress through the book, updated as the position is saved rather than rendering the book again
+    let progress = use_state(cx, || None::<Progress>);
+
+    // Whether the divider between book and definitions is being dragged
+    let resizing = use_ref(cx, || false);
+
+    let panel = use_ref(cx, || Panel::Definitions);
+    let last_recorded = use_ref(cx, || None);
 
-    let definitions = use_state(cx, Vec::new);
+    let known = use_ref(cx, HashSet::<String>::new);
+    // Unknown words in the current chapter, or None while they are being looked for
+    let density = use_ref(cx, || None::<Density>);
+    let density_generation = use_ref(cx, || 0_u32);
+    // Words of the current chapter, or None until it is segmented
+    let chapter_tokens = use_ref(cx, || None::<Vec<Token>>);
+
+    // Kept here so it survives switching between panels
+    let analysis = use_ref(cx, || AnalysisState::Idle);
+
+    // Text selected in the book, which can be highlighted
+    let selection = use_ref(cx, || None::<SelectedText>);
+    // Bumped whenever annotations change, so they are highlighted again
+    let annotation_revision = use_state(cx, || 0_u32);
+
+    let scan_mode = settings.read().scan_mode;
+    let layout = settings.read().layout;
 
     let has_document = read_state.read().is_some();
 
     // Set scroll after everything is rendered
     use_future(cx, (), |()| {
         let read_state = read_state.clone();
+        let resizing = resizing.clone();
+        let progress = progress.clone();
+
+        async move {
+            enable_scroll_callback(read_state, resizing, progress);
+        }
+    });
+
+    use_future(cx, (), |()| {
+        let read_state = read_state.clone();
+        let settings = settings.clone();
+        let resizing = resizing.clone();
+
+        async move {
+            enable_resize_callbacks(read_state, settings, resizing);
+        }
+    });
+
+    use_future(cx, (), |()| {
+        let read_state = read_state.clone();
+
+        async move {
+            reading_stats::enable_tracking(read_state);
+        }
+    });
+
+    use_future(cx, (), |()| {
+        let known = known.clone();
+
+        async move {
+            known_words::load(&known).await;
+        }
+    });
+
+    let page = read_state.with(|state| state.as_ref().map(ReaderState::get_page));
+    let book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    // Counting the book's characters takes a while, so it counts one chapter at a time once the chapter is shown
+    use_future(cx, (&book, &page), |_| {
+        let read_state = read_state.clone();
+        let progress = progress.clone();
+
+        async move {
+            loop {
+                // Let the reader render and respond in between chapters
+                sleep(0).await;
+
+                let counting = read_state
+                    .write_silent()
+                    .as_mut()
+                    .map_or(false, ReaderState::count_next_chapter);
+                if !counting {
+                    break;
+                }
+            }
+
+            let current = read_state.read().as_ref().and_then(ReaderState::progress);
+            progress.set(current);
+        }
+    });
+
+    // Measured once per book, since it barely changes while reading
+    let speed = use_future(cx, (&book,), |(book,)| async move {
+        match book {
+            Some(book) => reading_stats::reading_speed(&book).await,
+            None => None,
+        }
+    });
+    let known_count = known.read().len();
+    let highlight_unknown = settings.read().highlight_unknown;
+    let db_loaded = db.read().is_some();
+
+    // Segmenting is slow, so it is done once per chapter and only the highlight follows known words
+    use_future(
+        cx,
+        (&page, &highlight_unknown, &db_loaded),
+        |(_, enabled, loaded)| {
+            let known = known.clone();
+            let db = db.clone();
+            let reasons = reasons.clone();
+            let density = density.clone();
+            let density_generation = density_generation.clone();
+            let chapter_tokens = chapter_tokens.clone();
+
+            async move {
+                let generation = density_generation.with_mut(|g| {
+                    *g = g.wrapping_add(1);
+                    *g
+                });
+
+                chapter_tokens.set(None);
+                if !enabled || !loaded {
+                    highlight::clear(highlight::UNKNOWN);
+                    return;
+                }
+
+                density.set(None);
+
+                // Wait for the chapter to be rendered
+                sleep(0).await;
+
+                let is_current = || *density_generation.read() == generation;
+                let tokens = known_words::segment_chapter(&db, reasons.get(), is_current).await;
+                if !is_current() {
+                    return;
+                }
+
+                let result = tokens.as_ref().map(|tokens| {
+                    known.with(|known| known_words::highlight_unknown(known, tokens))
+                });
+                density.set(result);
+                chapter_tokens.set(tokens);
+            }
+        },
+    );
+
+    use_future(cx, (&known_count,), |_| {
+        let known = known.clone();
+        let density = density.clone();
+        let chapter_tokens = chapter_tokens.clone();
+
+        async move {
+            let result = chapter_tokens.with(|tokens| {
+                let tokens = tokens.as_ref()?;
+                Some(known.with(|known| known_words::highlight_unknown(known, tokens)))
+            });
+            if result.is_some() {
+                density.set(result);
+            }
+        }
+    });
+
+    use_future(cx, (&book, &page, annotation_revision.get()), |_| {
+        let read_state = read_state.clone();
+
+        async move {
+            // Wait for the chapter to be rendered
+            sleep(0).await;
+
+            annotations::apply(&read_state).await;
+        }
+    });
+
+    use_future(cx, (), |()| {
+        let popup = popup.clone();
 
         async move {
-            enable_scroll_callback(read_state);
+            enable_escape_callback(popup);
         }
     });
 
+    let target = LookupTarget {
+        definitions: definitions.clone(),
+        db: db.clone(),
+        reasons: reasons.clone(),
+        popup: popup.clone(),
+        settings: settings.clone(),
+        layout,
+        read_state: read_state.clone(),
+        panel: panel.clone(),
+        last_recorded: last_recorded.clone(),
+    };
+    let select_target = target.clone();
+    let hover_target = target;
+
+    let density_info = highlight_unknown.then(|| match *density.read() {
+        Some(Density { unknown, total }) => rsx!(p{
+            class: "text-sm text-gray-600 text-center",
+
+            "{unknown} of {total} words in this chapter are unknown"
+        }),
+        None => rsx!(p{
+            class: "text-sm text-gray-600 text-center",
+
+            "Finding unknown words"
+        }),
+    });
+
+    let selection_tools = selection.read().as_ref().map(|selected| {
+        let preview = selected.text.chars().take(20).collect::<String>();
+
+        rsx!(div{
+            class: "flex flex-wrap items-center justify-center gap-2 text-sm my-1",
+
+            span{ class: "text-gray-600", "Highlight “{preview}”" }
+
+            Color::ALL.into_iter().map(|color| {
+                let label = color.label();
+                let swatch = color.swatch();
+
+                rsx!(button{
+                    key: "{label}",
+                    class: "rounded-full px-3 {swatch}",
+
+                    onclick: move |_| {
+                        let Some(selected) = selection.write().take() else { return };
+
+                        // The highlight replaces the selection
+                        if let Some(s) = web_sys::window().and_then(|w| w.get_selection().ok().flatten()) {
+                            s.remove_all_ranges().ok();
+                        }
+
+                        let read_state = read_state.clone();
+                        let annotation_revision = annotation_revision.clone();
+                        let panel = panel.clone();
+                        wasm_bindgen_futures::spawn_local(async move {
+                            annotations::annotate(&read_state, selected, color).await;
+                            annotation_revision.modify(|r| r.wrapping_add(1));
+                            panel.set(Panel::Annotations);
+                        });
+                    },
+
+                    "{label}"
+                })
+            })
+
+            button{
+                class: "rounded-full px-3 bg-gray-200 hover:bg-gray-300",
+
+                onclick: move |_| selection.set(None),
+
+                "Cancel"
+            }
+        })
+    });
+
     let document = if has_document {
         rsx! {
             div{
                 class: "container mx-auto",
 
-                crate::nav::nav_component{ read_state: read_state }
+                crate::nav::nav_component{ read_state: read_state, progress: progress, speed: speed }
+                density_info
+                selection_tools
                 crate::view::view_component{
                     read_state: read_state,
-                    onselect: move |evt: String| {
-                        let reasons = reasons.clone();
-                        let defs = definitions.clone();
-                        let db = db.clone();
+                    scan_mode: scan_mode,
+                    onselect: move |scan: Scan| {
+                        let target = select_target.clone();
                         wasm_bindgen_futures::spawn_local(async move{
-                            update_defs_and_selection(&defs, &db, reasons.get(), &evt).await;
+                            if target.lookup(&scan).await {
+                                target.record(&scan).await;
+                            }
                         });
-                    }
+                    },
+                    onrangeselect: move |selected: Option<SelectedText>| {
+                        if *selection.read() != selected {
+                            selection.set(selected);
+                        }
+                    },
+                    onhover: move |scan: Scan| {
+                        // Moving within the same character shouldn't restart the lookup
+                        if hover_start.read().as_ref() == Some(&scan.start) {
+                            return;
+                        }
+                        hover_start.set(Some(scan.start.clone()));
+
+                        let generation = hover_generation.with_mut(|g| {
+                            *g = g.wrapping_add(1);
+                            *g
+                        });
+
+                        let target = hover_target.clone();
+                        let hover_generation = hover_generation.clone();
+                        wasm_bindgen_futures::spawn_local(async move{
+                            sleep(scan_mode.hover_delay()).await;
+
+                            let is_current = || *hover_generation.read() == generation;
+                            if !is_current() || !target.lookup(&scan).await {
+                                return;
+                            }
+
+                            // Passing over a word on the way elsewhere isn't worth remembering
+                            sleep(HOVER_RECORD_DELAY).await;
+                            if is_current() {
+                                target.record(&scan).await;
+                            }
+                        });
+                    },
                 }
-                crate::nav::nav_component{ read_state: read_state }
+                crate::nav::nav_component{ read_state: read_state, progress: progress, speed: speed }
             }
         }
     } else {
@@ -95,10 +701,124 @@ pub fn reader_component<'a>(cx: Scope<'a, ReaderProps<'a>>) -> Element<'a> {
         }}
     };
 
-    let info = info_state.with(|s| match s {
-        InfoState::Idle => {
-            rsx! {crate::definitions::definitions_component{ definitions: definitions.get() }}
+    let current_panel = *panel.read();
+    let tabs = rsx! {
+        nav{
+            class: "flex gap-1 mt-2",
+
+            Panel::ALL.into_iter().map(|p| {
+                let label = p.label();
+                let class = if p == current_panel {
+                    "bg-gray-300"
+                } else {
+                    "bg-gray-100 hover:bg-gray-200"
+                };
+
+                rsx!(button{
+                    key: "{label}",
+                    class: "text-sm rounded-full px-3 {class}",
+
+                    onclick: move |_| panel.set(p),
+
+                    "{label}"
+                })
+            })
         }
+    };
+
+    let info = info_state.with(|s| match s {
+        InfoState::Idle if current_panel == Panel::History => rsx! {
+            tabs
+            crate::history::history_component{ read_state: read_state }
+        },
+        InfoState::Idle if current_panel == Panel::Mining => rsx! {
+            tabs
+            crate::mining::mining_component{}
+        },
+        InfoState::Idle if current_panel == Panel::Vocabulary => rsx! {
+            tabs
+            crate::vocabulary::vocabulary_component{
+                analysis: analysis,
+                read_state: read_state,
+                db: db,
+                reasons: reasons,
+                known: known,
+            }
+        },
+        InfoState::Idle if current_panel == Panel::Sentences => rsx! {
+            tabs
+            crate::sentences::sentences_component{
+                analysis: analysis,
+                read_state: read_state,
+                db: db,
+                reasons: reasons,
+                known: known,
+            }
+        },
+        InfoState::Idle if current_panel == Panel::Contents => rsx! {
+            tabs
+            crate::contents::contents_component{
+                read_state: read_state,
+                analysis: analysis,
+                known: known,
+            }
+        },
+        InfoState::Idle if current_panel == Panel::Find => rsx! {
+            tabs
+            crate::book_search::book_search_component{ read_state: read_state }
+        },
+        InfoState::Idle if current_panel == Panel::Bookmarks => rsx! {
+            tabs
+            crate::bookmarks::bookmarks_component{ read_state: read_state }
+        },
+        InfoState::Idle if current_panel == Panel::Annotations => rsx! {
+            tabs
+            crate::annotations::annotations_component{
+                read_state: read_state,
+                revision: annotation_revision,
+            }
+        },
+        InfoState::Idle if current_panel == Panel::Library => rsx! {
+            tabs
+            crate::library::library_component{
+                read_state: read_state,
+                analysis: analysis,
+                known: known,
+            }
+        },
+        InfoState::Idle if current_panel == Panel::Statistics => rsx! {
+            tabs
+            crate::reading_stats::statistics_component{ read_state: read_state }
+        },
+        InfoState::Idle if current_panel == Panel::Activity => rsx! {
+            tabs
+            crate::reading_goals::activity_component{}
+        },
+        InfoState::Idle if current_panel == Panel::Known => rsx! {
+            tabs
+            crate::known_import::known_import_component{ known: known, db: db, reasons: reasons }
+        },
+        InfoState::Idle => rsx! {
+            tabs
+            crate::search::search_component{ lookups: definitions, db: db, reasons: reasons, read_state: read_state }
+            crate::definitions::definitions_component{
+                lookups: definitions,
+                known: known,
+                onlookup: move |scan: Scan| {
+                    let definitions = definitions.clone();
+                    let read_state = read_state.clone();
+                    let db = db.clone();
+                    let reasons = reasons.clone();
+                    let normalization = settings.read().normalization;
+                    wasm_bindgen_futures::spawn_local(async move{
+                        if push_lookup(&definitions, &db, reasons.get(), normalization, &scan).await {
+                            // Not a position in the book, so only the text around it is kept
+                            history::record(&definitions, &read_state, scan.text.clone(), scan.sentence().unwrap_or_default(), None).await;
+                        }
+                    });
+                }
+            }
+        },
         InfoState::LoadDB => rsx! {p{"Loading DB. Please wait"}},
         InfoState::LoadDict(LoadDictState::ParsingDict) => {
             rsx! {p{"Parsing dictionary. Please wait"}}
@@ -110,25 +830,113 @@ pub fn reader_component<'a>(cx: Scope<'a, ReaderProps<'a>>) -> Element<'a> {
             let (current, total) = (current.to_owned(), total.to_owned());
             rsx! {p{"Loading Dictionary: {current}/{total} Please wait"}}
         }
+        InfoState::LoadDict(LoadDictState::BuildingIndex(current, total)) => {
+            let (current, total) = (current.to_owned(), total.to_owned());
+            rsx! {p{"Indexing Dictionary: {current}/{total} Please wait"}}
+        }
     });
 
-    cx.render(rsx! {
-        div{
-            class: "px-4 h-3/5 overflow-y-scroll",
-            id: "reader-scroll",
+    let idle = *info_state.read() == InfoState::Idle;
+    let popup_style = popup.read().clone();
 
-            document
-        }
+    let split = settings.read().split;
+    let collapsed = settings.read().definitions_collapsed;
+    let (toggle_label, reader_basis) = if collapsed {
+        ("Show definitions", 100.0)
+    } else {
+        ("Hide definitions", split)
+    };
+
+    let (reader_class, panel) = match layout {
+        Layout::Split => (
+            "px-4 flex-none min-h-0 min-w-0 overflow-y-scroll",
+            rsx! {
+                div{
+                    class: "flex-none flex lg:flex-col items-center justify-center gap-2 p-1 bg-gray-100 cursor-row-resize lg:cursor-col-resize select-none touch-none",
+
+                    onpointerdown: move |_| {
+                        if !collapsed {
+                            resizing.set(true);
+                        }
+                    },
+
+                    div{ class: "h-1 w-8 lg:h-8 lg:w-1 rounded-full bg-gray-400" }
+                    button{
+                        class: "text-sm px-2 rounded-full bg-gray-200 hover:bg-gray-300 lg:[writing-mode:vertical-lr]",
+
+                        onpointerdown: |evt| evt.stop_propagation(),
+                        onclick: move |_| {
+                            settings.with_mut(|s| {
+                                s.definitions_collapsed = !s.definitions_collapsed;
+                                s.save();
+                            });
+                            // The book pane changes size, so go back to where the reader was
+                            read_state.with_mut(|state| state.as_mut().map(ReaderState::restore_position));
+                        },
+
+                        "{toggle_label}"
+                    }
+                }
+
+                (!collapsed).then(|| rsx! {
+                    div{
+                        class: "px-4 flex-1 min-h-0 min-w-0 overflow-y-scroll bg-gray-50 rounded-md border-2",
+
+                        div{
+                            class: "container mx-auto",
 
+                            info
+                        }
+                    }
+                })
+            },
+        ),
+        Layout::Popup => (
+            "px-4 h-full w-full overflow-y-scroll",
+            match popup_style {
+                _ if !idle => rsx! {
+                    div{
+                        class: "fixed inset-x-0 bottom-0 z-10 px-4 bg-gray-50 rounded-md border-2",
+
+                        info
+                    }
+                },
+                Some(style) if !definitions.read().is_empty() => rsx! {
+                    div{
+                        class: "fixed z-10 w-80 max-w-full px-2 overflow-y-scroll bg-gray-50 rounded-md border-2 shadow-lg",
+                        style: "{style}",
+
+                        info
+                    }
+                },
+                _ => rsx! { Fragment{} },
+            },
+        ),
+    };
+
+    let reader_style = match layout {
+        Layout::Split => format!("flex-basis: {reader_basis}%;"),
+        Layout::Popup => String::new(),
+    };
+
+    cx.render(rsx! {
         div{
-            class: "px-4 h-2/5 overflow-y-scroll bg-gray-50 rounded-md border-2",
+            class: "flex flex-col lg:flex-row h-full",
+            id: "reader-split",
 
             div{
-                class: "container mx-auto",
+                class: "{reader_class}",
+                style: "{reader_style}",
+                id: "reader-scroll",
 
-                info
+                // Tapping outside of the popup or scrolling away closes it
+                onclick: move |_| close_popup(popup),
+                onscroll: move |_| close_popup(popup),
+
+                document
             }
-        }
 
+            panel
+        }
     })
 }
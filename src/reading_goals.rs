@@ -0,0 +1,513 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+};
+
+use dioxus::prelude::*;
+use wasm_bindgen::JsValue;
+
+use crate::{
+    reading_stats::{self, format_duration, Totals},
+    user_db::{self, DailyGoal, GoalUnit, ReadingSession},
+};
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+// Weeks shown in the calendar, about half a year
+const CALENDAR_WEEKS: i64 = 26;
+
+impl GoalUnit {
+    pub const ALL: [Self; 2] = [Self::Characters, Self::Minutes];
+
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Characters => "characters",
+            Self::Minutes => "minutes",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|unit| unit.key() == key)
+    }
+
+    /// How much of a goal in this unit `totals` are.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn amount(self, totals: &Totals) -> f64 {
+        match self {
+            Self::Characters => totals.characters as f64,
+            Self::Minutes => totals.active / (60.0 * 1000.0),
+        }
+    }
+}
+
+/// Share of `goal` reached by `totals`, where 1.0 is the goal met.
+fn goal_share(goal: DailyGoal, totals: &Totals) -> f64 {
+    goal.unit.amount(totals) / f64::from(goal.amount.max(1))
+}
+
+/// Local day of `timestamp`, counted in days since the epoch.
+#[allow(clippy::cast_possible_truncation)]
+fn local_day(timestamp: f64) -> i64 {
+    let offset = js_sys::Date::new(&JsValue::from_f64(timestamp)).get_timezone_offset();
+    ((timestamp - offset * 60.0 * 1000.0) / DAY_MS).floor() as i64
+}
+
+fn today() -> i64 {
+    local_day(js_sys::Date::now())
+}
+
+/// Timestamp of the local midnight that started today.
+fn today_start() -> f64 {
+    let date = js_sys::Date::new_0();
+    date.set_hours(0);
+    date.set_minutes(0);
+    date.set_seconds(0);
+    date.set_milliseconds(0)
+}
+
+thread_local! {
+    // Told when the goal changes, so the header shows it right away
+    static GOAL_LISTENER: RefCell<Option<Box<dyn Fn(Option<DailyGoal>)>>> = RefCell::new(None);
+}
+
+fn goal_changed(goal: Option<DailyGoal>) {
+    GOAL_LISTENER.with(|listener| {
+        if let Some(listener) = listener.borrow().as_ref() {
+            listener(goal);
+        }
+    });
+}
+
+/// Date of `day` as counted by `local_day`, whose UTC fields are the local ones.
+#[allow(clippy::cast_precision_loss)]
+fn day_date(day: i64) -> js_sys::Date {
+    js_sys::Date::new(&JsValue::from_f64(day as f64 * DAY_MS))
+}
+
+fn format_day(day: i64) -> String {
+    let date = day_date(day);
+    format!(
+        "{}-{:02}-{:02}",
+        date.get_utc_full_year(),
+        date.get_utc_month() + 1,
+        date.get_utc_date()
+    )
+}
+
+/// What was read on each day, by the day a session started.
+pub fn daily(sessions: &[ReadingSession]) -> BTreeMap<i64, Totals> {
+    daily_by(sessions, local_day)
+}
+
+/// Like `daily`, with the day of a timestamp told by `day_of`.
+fn daily_by(sessions: &[ReadingSession], day_of: impl Fn(f64) -> i64) -> BTreeMap<i64, Totals> {
+    let mut days = BTreeMap::<i64, Vec<&ReadingSession>>::new();
+    for session in sessions {
+        days.entry(day_of(session.start)).or_default().push(session);
+    }
+
+    days.into_iter()
+        .map(|(day, sessions)| (day, Totals::of(sessions)))
+        .collect()
+}
+
+/// Days in a row with some reading.
+pub struct Streaks {
+    /// Ending today, or yesterday if nothing was read yet today
+    pub current: usize,
+    pub longest: usize,
+}
+
+pub fn streaks(days: &BTreeMap<i64, Totals>, today: i64) -> Streaks {
+    let read = days
+        .iter()
+        .filter(|(_, totals)| totals.active > 0.0 || totals.characters > 0)
+        .map(|(&day, _)| day)
+        .collect::<HashSet<_>>();
+
+    let mut longest = 0;
+    for &day in &read {
+        // Only count from the start of each streak
+        if read.contains(&(day - 1)) {
+            continue;
+        }
+        let length = (day..).take_while(|d| read.contains(d)).count();
+        longest = longest.max(length);
+    }
+
+    let end = if read.contains(&today) {
+        today
+    } else {
+        today - 1
+    };
+    let current = (0..).take_while(|i| read.contains(&(end - i))).count();
+
+    Streaks { current, longest }
+}
+
+/// Background of a calendar day, darker the more was read.
+fn heat_class(share: f64) -> &'static str {
+    match share {
+        s if s <= 0.0 => "bg-gray-100",
+        s if s < 0.5 => "bg-green-200",
+        s if s < 1.0 => "bg-green-400",
+        s if s < 2.0 => "bg-green-600",
+        _ => "bg-green-800",
+    }
+}
+
+#[inline_props]
+fn calendar(
+    cx: Scope,
+    days: BTreeMap<i64, Totals>,
+    goal: Option<DailyGoal>,
+    today: i64,
+) -> Element {
+    // Without a goal, days are compared to the busiest one shown
+    let first = today - CALENDAR_WEEKS * 7 + 1;
+    let busiest = days
+        .range(first..=*today)
+        .map(|(_, totals)| GoalUnit::Characters.amount(totals))
+        .fold(0.0, f64::max);
+    let share = |totals: &Totals| match goal {
+        Some(goal) => goal_share(*goal, totals),
+        None if busiest > 0.0 => GoalUnit::Characters.amount(totals) / busiest * 1.5,
+        None => 0.0,
+    };
+
+    // Columns are weeks starting on Sunday, so the first one may start before `first`
+    let start = first - i64::from(day_date(first).get_utc_day());
+    let weeks = (start..=*today).step_by(7).collect::<Vec<_>>();
+
+    cx.render(rsx! {
+        div{
+            class: "flex gap-1 overflow-x-auto py-2",
+
+            weeks.into_iter().map(|week| rsx!(div{
+                key: "{week}",
+                class: "flex flex-col gap-1",
+
+                (week..week + 7).map(|day| {
+                    let empty = Totals::default();
+                    let totals = days.get(&day).unwrap_or(&empty);
+                    let class = if day > *today { "invisible" } else { heat_class(share(totals)) };
+                    let date = format_day(day);
+                    let time = format_duration(totals.active);
+                    let characters = totals.characters;
+
+                    rsx!(div{
+                        key: "{day}",
+                        class: "w-3 h-3 rounded-sm {class}",
+                        title: "{date}: {characters} characters, {time}",
+                    })
+                })
+            }))
+        }
+    })
+}
+
+#[inline_props]
+fn goal_form<'a>(
+    cx: Scope<'a>,
+    goal: Option<DailyGoal>,
+    onsave: EventHandler<'a, Option<DailyGoal>>,
+) -> Element<'a> {
+    let amount = use_state(cx, || {
+        goal.map_or_else(String::new, |g| g.amount.to_string())
+    });
+    let unit = use_state(cx, || goal.map_or(GoalUnit::Characters, |g| g.unit));
+
+    let unit_key = unit.get().key();
+
+    cx.render(rsx! {
+        form{
+            class: "flex flex-wrap items-center gap-2",
+
+            prevent_default: "onsubmit",
+            onsubmit: move |_| {
+                // Setting nothing or zero removes the goal
+                let goal = amount
+                    .get()
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&amount| amount > 0)
+                    .map(|amount| DailyGoal { amount, unit: *unit.get() });
+                onsave.call(goal);
+            },
+
+            "Daily goal"
+
+            input{
+                class: "w-24 px-2 rounded border-2",
+
+                r#type: "number",
+                min: "0",
+                placeholder: "None",
+                value: "{amount}",
+                oninput: move |evt: FormEvent| amount.set(evt.value.clone()),
+            }
+
+            select{
+                class: "bg-gray-100 rounded p-1",
+
+                value: "{unit_key}",
+                onchange: move |evt: FormEvent| {
+                    if let Some(u) = GoalUnit::from_key(&evt.value) {
+                        unit.set(u);
+                    }
+                },
+
+                GoalUnit::ALL.into_iter().map(|u| {
+                    let key = u.key();
+                    rsx!(option{ key: "{key}", value: "{key}", "{key}" })
+                })
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+                r#type: "submit",
+
+                "Set goal"
+            }
+        }
+    })
+}
+
+/// Loads everything the activity dashboard shows.
+async fn load_activity() -> Result<(Vec<ReadingSession>, Option<DailyGoal>), user_db::UserDbError> {
+    Ok((
+        reading_stats::load_sessions().await?,
+        user_db::get_daily_goal().await?,
+    ))
+}
+
+/// Calendar of what was read each day, with streaks and the daily goal.
+pub fn activity_component(cx: Scope) -> Element {
+    // Bumped when the goal changes, so it is loaded again
+    let revision = use_state(cx, || 0_u32);
+
+    let activity = use_future(cx, (revision.get(),), |_| load_activity());
+
+    let (sessions, goal) = match activity.value() {
+        None => return cx.render(rsx!(p{"Loading activity"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load activity: {e}"}));
+        }
+        Some(Ok((sessions, goal))) => (sessions, *goal),
+    };
+
+    let today = today();
+    let days = daily(sessions);
+    let Streaks { current, longest } = streaks(&days, today);
+
+    let empty = Totals::default();
+    let read_today = days.get(&today).unwrap_or(&empty);
+    let today_summary = read_today.summary();
+    let goal_summary = goal.map(|goal| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let percent = (goal_share(goal, read_today) * 100.0).round() as u32;
+        format!(
+            ", {percent}% of the daily goal of {} {}",
+            goal.amount,
+            goal.unit.key()
+        )
+    });
+    let goal_summary = goal_summary.unwrap_or_default();
+
+    cx.render(rsx! {
+        p{
+            class: "mt-2",
+
+            "Today: {today_summary}{goal_summary}"
+        }
+
+        p{
+            class: "text-sm text-gray-600",
+
+            "Current streak: {current} days, longest streak: {longest} days"
+        }
+
+        calendar{ days: days, goal: goal, today: today }
+
+        goal_form{
+            key: "{revision}",
+            goal: goal,
+            onsave: move |goal: Option<DailyGoal>| {
+                let revision = revision.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match user_db::put_daily_goal(goal.as_ref()).await {
+                        Ok(()) => goal_changed(goal),
+                        Err(e) => log::error!("Cannot save daily goal due to error {e}"),
+                    }
+                    revision.modify(|r| r.wrapping_add(1));
+                });
+            },
+        }
+    })
+}
+
+/// Today's progress towards the daily goal, shown in the header if there is one.
+/// Follows the sessions as they are saved, rather than loading them again.
+pub fn goal_progress_component(cx: Scope) -> Element {
+    let goal = use_state(cx, || None::<DailyGoal>);
+    // Sessions started today, including the one being read
+    let sessions = use_ref(cx, Vec::<ReadingSession>::new);
+
+    use_future(cx, (), |()| {
+        let goal = goal.clone();
+        let sessions = sessions.clone();
+
+        async move {
+            let sessions_moved = sessions.clone();
+            reading_stats::on_save(move |session| {
+                sessions_moved.with_mut(|sessions| {
+                    match sessions.iter_mut().find(|s| s.start == session.start) {
+                        Some(saved) => *saved = session.clone(),
+                        None => sessions.push(session.clone()),
+                    }
+                });
+            });
+
+            let goal_moved = goal.clone();
+            GOAL_LISTENER.with(|listener| {
+                *listener.borrow_mut() = Some(Box::new(move |g| goal_moved.set(g)));
+            });
+
+            match user_db::get_daily_goal().await {
+                Ok(g) => goal.set(g),
+                Err(e) => log::error!("Cannot load daily goal due to error {e}"),
+            }
+
+            match user_db::get_reading_sessions_since(today_start()).await {
+                // Sessions saved meanwhile are newer than the loaded ones
+                Ok(loaded) => sessions.with_mut(|sessions| {
+                    for session in loaded {
+                        if !sessions.iter().any(|s| s.start == session.start) {
+                            sessions.push(session);
+                        }
+                    }
+                }),
+                Err(e) => log::error!("Cannot load reading progress due to error {e}"),
+            }
+        }
+    });
+
+    let Some(goal) = *goal.get() else {
+        return None;
+    };
+
+    let today = today();
+    let amount = sessions.with(|sessions| {
+        let totals = Totals::of(sessions.iter().filter(|s| local_day(s.start) == today));
+        goal.unit.amount(&totals)
+    });
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (amount, percent) = (
+        amount.floor() as u64,
+        (amount * 100.0 / f64::from(goal.amount.max(1)))
+            .min(100.0)
+            .round() as u32,
+    );
+    let unit = goal.unit.key();
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-col justify-center h-full text-sm",
+            title: "Daily reading goal",
+
+            "Today {amount}/{goal.amount} {unit}"
+
+            div{
+                class: "w-32 h-2 bg-gray-200 rounded-full",
+
+                div{
+                    class: "h-2 bg-green-500 rounded-full",
+                    style: "width: {percent}%",
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(start: f64, minutes: f64, characters: usize) -> ReadingSession {
+        ReadingSession {
+            book: "book".to_string(),
+            start,
+            end: start + minutes * 60.0 * 1000.0,
+            active: minutes * 60.0 * 1000.0,
+            characters,
+        }
+    }
+
+    fn days(read: &[i64]) -> BTreeMap<i64, Totals> {
+        read.iter()
+            .map(|&day| (day, Totals::of(&[session(0.0, 10.0, 100)])))
+            .collect()
+    }
+
+    #[test]
+    fn groups_sessions_by_day() {
+        let sessions = [
+            session(DAY_MS + 1.0, 10.0, 100),
+            session(DAY_MS * 1.5, 20.0, 200),
+            session(DAY_MS * 3.0, 5.0, 50),
+        ];
+        #[allow(clippy::cast_possible_truncation)]
+        let days = daily_by(&sessions, |timestamp| (timestamp / DAY_MS).floor() as i64);
+
+        assert_eq!(days.keys().copied().collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(days[&1].sessions, 2);
+        assert_eq!(days[&1].characters, 300);
+        assert_eq!(days[&3].characters, 50);
+    }
+
+    #[test]
+    fn counts_streaks() {
+        let Streaks { current, longest } = streaks(&days(&[1, 2, 3, 5, 6]), 6);
+        assert_eq!((current, longest), (2, 3));
+    }
+
+    #[test]
+    fn keeps_the_streak_until_today_ends() {
+        // Nothing read yet today, but yesterday
+        let Streaks { current, .. } = streaks(&days(&[4, 5]), 6);
+        assert_eq!(current, 2);
+
+        let Streaks { current, longest } = streaks(&days(&[1, 2]), 6);
+        assert_eq!((current, longest), (0, 2));
+    }
+
+    #[test]
+    fn ignores_empty_days() {
+        let mut days = days(&[1, 2]);
+        days.insert(3, Totals::default());
+
+        let Streaks { current, longest } = streaks(&days, 3);
+        assert_eq!((current, longest), (2, 2));
+        assert_eq!(streaks(&BTreeMap::new(), 3).longest, 0);
+    }
+
+    #[test]
+    fn measures_goals() {
+        let totals = Totals::of(&[session(0.0, 30.0, 5000)]);
+        let characters = DailyGoal {
+            amount: 10000,
+            unit: GoalUnit::Characters,
+        };
+        let minutes = DailyGoal {
+            amount: 20,
+            unit: GoalUnit::Minutes,
+        };
+
+        assert!((goal_share(characters, &totals) - 0.5).abs() < 1e-9);
+        assert!((goal_share(minutes, &totals) - 1.5).abs() < 1e-9);
+        assert_eq!(heat_class(0.0), "bg-gray-100");
+        assert_eq!(heat_class(1.5), "bg-green-600");
+    }
+}
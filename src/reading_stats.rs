@@ -0,0 +1,482 @@
+extern crate web_sys;
+
+use std::{cell::RefCell, collections::HashSet};
+
+use dioxus::prelude::*;
+use wasm_bindgen::{prelude::Closure, JsCast};
+
+use crate::{
+    export,
+    read_state::ReaderState,
+    user_db::{self, ReadingSession, UserDbError},
+};
+
+// Longer pauses without any input mean the reader looked away, so they don't count as reading
+const IDLE_TIMEOUT: f64 = 3.0 * 60.0 * 1000.0;
+// Reading again after a longer break starts a new session
+const SESSION_BREAK: f64 = 30.0 * 60.0 * 1000.0;
+// Moving through the book faster than this is skimming or jumping, not reading
+const MAX_CHARS_PER_SECOND: f64 = 30.0;
+// Less reading than this gives a speed too far off to show
+const MIN_SPEED_TIME: f64 = 60.0 * 1000.0;
+
+/// The session being read, and what is needed to extend it.
+struct Tracker {
+    session: ReadingSession,
+    /// When the reader last did something, or None while paused
+    last_activity: Option<f64>,
+    /// Chapter and character offset read at last, and when
+    position: Option<(usize, usize, f64)>,
+}
+
+thread_local! {
+    static TRACKER: RefCell<Option<Tracker>> = RefCell::new(None);
+    // Told about every session saved, so what was read can be shown as it changes
+    static SAVE_LISTENER: RefCell<Option<Box<dyn Fn(&ReadingSession)>>> = RefCell::new(None);
+}
+
+/// Calls `listener` with every session saved from now on.
+/// It is called while the tracker is updated, so it must not look at the tracker itself.
+pub fn on_save(listener: impl Fn(&ReadingSession) + 'static) {
+    SAVE_LISTENER.with(|l| *l.borrow_mut() = Some(Box::new(listener)));
+}
+
+fn save(session: ReadingSession) {
+    // Opening a book without reading it isn't a session
+    if session.active <= 0.0 && session.characters == 0 {
+        return;
+    }
+
+    SAVE_LISTENER.with(|listener| {
+        if let Some(listener) = listener.borrow().as_ref() {
+            listener(&session);
+        }
+    });
+
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = user_db::put_reading_session(&session).await {
+            log::error!("Cannot save reading session due to error {e}");
+        }
+    });
+}
+
+/// Counts the time since the last activity in `book` as reading, unless the reader was idle.
+fn activity(book: &str) {
+    let now = js_sys::Date::now();
+
+    TRACKER.with(|tracker| {
+        let mut tracker = tracker.borrow_mut();
+
+        let continues = tracker.as_ref().map_or(false, |t| {
+            t.session.book == book && now - t.session.end < SESSION_BREAK
+        });
+        if !continues {
+            if let Some(finished) = tracker.take() {
+                save(finished.session);
+            }
+
+            *tracker = Some(Tracker {
+                session: ReadingSession {
+                    book: book.to_string(),
+                    start: now,
+                    end: now,
+                    active: 0.0,
+                    characters: 0,
+                },
+                last_activity: None,
+                position: None,
+            });
+        }
+
+        let Some(t) = tracker.as_mut() else { return };
+        if let Some(idle) = t.last_activity.map(|last| now - last) {
+            if idle < IDLE_TIMEOUT {
+                t.session.active += idle;
+            }
+        }
+        t.last_activity = Some(now);
+        t.session.end = now;
+    });
+}
+
+/// Stops counting time until the next activity, eg because the tab was hidden.
+fn pause() {
+    let now = js_sys::Date::now();
+
+    TRACKER.with(|tracker| {
+        let mut tracker = tracker.borrow_mut();
+        let Some(t) = tracker.as_mut() else { return };
+
+        if let Some(idle) = t.last_activity.take().map(|last| now - last) {
+            if idle < IDLE_TIMEOUT {
+                t.session.active += idle;
+                t.session.end = now;
+            }
+        }
+
+        save(t.session.clone());
+    });
+}
+
+/// Counts the characters between the position read at last and the one just saved in `state`.
+/// Only reading forward within a chapter or into the next one counts.
+pub fn record_position(state: &mut ReaderState) {
+    let book = state.get_title();
+    let (page, offset) = (state.get_page(), state.get_offset());
+
+    activity(&book);
+    let now = js_sys::Date::now();
+
+    TRACKER.with(|tracker| {
+        let mut tracker = tracker.borrow_mut();
+        let Some(t) = tracker.as_mut() else { return };
+
+        if let Some((last_page, last_offset, last_time)) = t.position {
+            let read = if page == last_page {
+                offset.checked_sub(last_offset)
+            } else if page == last_page + 1 {
+                state
+                    .chapter_length(last_page)
+                    .map(|len| len.saturating_sub(last_offset) + offset)
+            } else {
+                None
+            };
+
+            let seconds = (now - last_time) / 1000.0;
+            #[allow(clippy::cast_precision_loss)]
+            let plausible = read.filter(|&read| read as f64 <= seconds * MAX_CHARS_PER_SECOND);
+            if let Some(read) = plausible {
+                t.session.characters += read;
+            }
+        }
+
+        t.position = Some((page, offset, now));
+        save(t.session.clone());
+    });
+}
+
+/// The session being read, which may not be saved completely yet.
+pub fn current_session() -> Option<ReadingSession> {
+    TRACKER.with(|tracker| tracker.borrow().as_ref().map(|t| t.session.clone()))
+}
+
+/// All reading sessions, oldest first, with the current one up to date.
+pub async fn load_sessions() -> Result<Vec<ReadingSession>, UserDbError> {
+    let mut sessions = user_db::get_reading_sessions().await?;
+
+    if let Some(current) = current_session() {
+        match sessions.iter_mut().find(|s| s.start == current.start) {
+            Some(saved) => *saved = current,
+            None => sessions.push(current),
+        }
+    }
+
+    Ok(sessions)
+}
+
+/// Characters read per hour in `book`, or in all books if `book` wasn't read enough yet.
+pub async fn reading_speed(book: &str) -> Option<f64> {
+    let sessions = match load_sessions().await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            log::error!("Cannot load reading sessions due to error {e}");
+            return None;
+        }
+    };
+
+    Totals::of(sessions.iter().filter(|s| s.book == book))
+        .speed()
+        .or_else(|| Totals::of(&sessions).speed())
+}
+
+/// Counts input anywhere in the page as activity in the open book, and pauses while the tab is hidden.
+pub fn enable_tracking(read_state: UseRef<Option<ReaderState>>) {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let activity_callback = Closure::<dyn Fn()>::new(move || {
+        if let Some(book) = read_state.with(|state| state.as_ref().map(ReaderState::get_title)) {
+            activity(&book);
+        }
+    });
+
+    for event in [
+        "pointerdown",
+        "pointermove",
+        "keydown",
+        "wheel",
+        "touchstart",
+    ] {
+        window
+            .add_event_listener_with_callback(event, activity_callback.as_ref().unchecked_ref())
+            .unwrap();
+    }
+    // Scrolling the book doesn't bubble up to the window, but can be captured
+    window
+        .add_event_listener_with_callback_and_bool(
+            "scroll",
+            activity_callback.as_ref().unchecked_ref(),
+            true,
+        )
+        .unwrap();
+
+    let document_moved = document.clone();
+    let visibility_callback = Closure::<dyn Fn()>::new(move || {
+        if document_moved.hidden() {
+            pause();
+        }
+    });
+
+    document
+        .add_event_listener_with_callback(
+            "visibilitychange",
+            visibility_callback.as_ref().unchecked_ref(),
+        )
+        .unwrap();
+
+    activity_callback.forget();
+    visibility_callback.forget();
+}
+
+/// Time read and characters read over some sessions.
+#[derive(Default, PartialEq)]
+pub struct Totals {
+    pub sessions: usize,
+    /// Milliseconds spent actively reading
+    pub active: f64,
+    pub characters: usize,
+}
+
+impl Totals {
+    pub fn of<'a>(sessions: impl IntoIterator<Item = &'a ReadingSession>) -> Self {
+        sessions
+            .into_iter()
+            .fold(Self::default(), |mut totals, session| {
+                totals.sessions += 1;
+                totals.active += session.active;
+                totals.characters += session.characters;
+                totals
+            })
+    }
+
+    /// Characters read per hour, once there was enough reading to tell.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn speed(&self) -> Option<f64> {
+        (self.active >= MIN_SPEED_TIME)
+            .then(|| self.characters as f64 / (self.active / (60.0 * 60.0 * 1000.0)))
+    }
+
+    pub fn summary(&self) -> String {
+        let time = format_duration(self.active);
+        let speed = self.speed().map_or_else(String::new, |speed| {
+            format!(", {speed:.0} characters per hour")
+        });
+
+        format!("{time}, {} characters{speed}", self.characters)
+    }
+}
+
+/// Formats milliseconds as hours and minutes.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn format_duration(ms: f64) -> String {
+    let minutes = (ms / (60.0 * 1000.0)).round().max(0.0) as u64;
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes} min"),
+        (hours, minutes) => format!("{hours} h {minutes} min"),
+    }
+}
+
+fn export_csv(sessions: &[ReadingSession]) {
+    let header = ["Book", "Start", "End", "Minutes", "Characters"]
+        .map(ToString::to_string)
+        .to_vec();
+    let rows = sessions.iter().map(|s| {
+        vec![
+            s.book.clone(),
+            export::format_timestamp(s.start),
+            export::format_timestamp(s.end),
+            format!("{:.1}", s.active / (60.0 * 1000.0)),
+            s.characters.to_string(),
+        ]
+    });
+
+    export::download(
+        "reading sessions.csv",
+        "text/csv",
+        &export::csv(std::iter::once(header).chain(rows)),
+    );
+}
+
+fn export_json(sessions: &[ReadingSession]) {
+    match serde_json::to_string_pretty(sessions) {
+        Ok(json) => export::download("reading sessions.json", "application/json", &json),
+        Err(e) => log::error!("Cannot export reading sessions due to error {e}"),
+    }
+}
+
+#[derive(Props)]
+pub struct StatisticsProps<'a> {
+    read_state: &'a UseRef<Option<ReaderState>>,
+}
+
+/// Time spent reading, characters read and reading speed, overall and per book.
+pub fn statistics_component<'a>(cx: Scope<'a, StatisticsProps<'a>>) -> Element<'a> {
+    let read_state = cx.props.read_state;
+
+    let sessions = use_future(cx, (), |()| load_sessions());
+
+    let current_book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let sessions = match sessions.value() {
+        None => return cx.render(rsx!(p{"Loading statistics"})),
+        Some(Err(e)) => {
+            let e = e.to_string();
+            return cx.render(rsx!(p{"Cannot load statistics: {e}"}));
+        }
+        Some(Ok(sessions)) => sessions,
+    };
+
+    if sessions.is_empty() {
+        return cx.render(rsx!(p{
+            class: "mt-2",
+
+            "Nothing read yet. Time and characters are counted while you read."
+        }));
+    }
+
+    let current = current_session()
+        .filter(|s| s.active > 0.0 || s.characters > 0)
+        .map(|s| Totals::of([&s]).summary());
+    let overall = Totals::of(sessions);
+
+    // Most recently read books first
+    let mut books = sessions.iter().map(|s| s.book.as_str()).collect::<Vec<_>>();
+    books.reverse();
+    let mut seen = HashSet::new();
+    books.retain(|book| seen.insert(*book));
+
+    let overall_summary = overall.summary();
+    let session_count = overall.sessions;
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-wrap items-center gap-2 mt-2",
+
+            p{
+                class: "flex-1",
+
+                "{overall_summary} in {session_count} sessions"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_csv(sessions),
+
+                "Export CSV"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_json(sessions),
+
+                "Export JSON"
+            }
+        }
+
+        current.map(|current| rsx!(p{
+            class: "text-sm text-gray-600",
+
+            "This session: {current}"
+        }))
+
+        ul{
+            class: "list-none mt-2",
+
+            books.into_iter().map(|book| {
+                let totals = Totals::of(sessions.iter().filter(|s| s.book == book));
+                let summary = totals.summary();
+                let count = totals.sessions;
+                let open = if Some(book) == current_book.as_deref() { " (open)" } else { "" };
+
+                rsx!(li{
+                    key: "{book}",
+                    class: "border-b py-1",
+
+                    h2{
+                        class: "text-lg font-medium",
+
+                        "{book}{open}"
+                    }
+
+                    p{ "{summary}" }
+                    p{
+                        class: "text-sm text-gray-600",
+
+                        "{count} sessions"
+                    }
+                })
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: f64 = 60.0 * 1000.0;
+
+    fn session(book: &str, minutes: f64, characters: usize) -> ReadingSession {
+        ReadingSession {
+            book: book.to_string(),
+            start: 0.0,
+            end: minutes * MINUTE,
+            active: minutes * MINUTE,
+            characters,
+        }
+    }
+
+    #[test]
+    fn sums_sessions() {
+        let sessions = [session("a", 10.0, 1000), session("b", 20.0, 3000)];
+        let totals = Totals::of(&sessions);
+
+        assert_eq!(totals.sessions, 2);
+        assert!((totals.active - 30.0 * MINUTE).abs() < f64::EPSILON);
+        assert_eq!(totals.characters, 4000);
+        assert!(Totals::of([]) == Totals::default());
+    }
+
+    #[test]
+    fn measures_speed_per_hour() {
+        let totals = Totals::of(&[session("a", 30.0, 6000)]);
+        let speed = totals.speed().unwrap();
+        assert!((speed - 12000.0).abs() < 1e-6);
+
+        // Too little reading to tell
+        assert_eq!(Totals::of(&[session("a", 0.5, 100)]).speed(), None);
+    }
+
+    #[test]
+    fn summarizes() {
+        let totals = Totals::of(&[session("a", 90.0, 15000)]);
+        assert_eq!(
+            totals.summary(),
+            "1 h 30 min, 15000 characters, 10000 characters per hour"
+        );
+        assert_eq!(Totals::default().summary(), "0 min, 0 characters");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0.0), "0 min");
+        assert_eq!(format_duration(29.0 * 1000.0), "0 min");
+        assert_eq!(format_duration(45.0 * MINUTE), "45 min");
+        assert_eq!(format_duration(60.0 * MINUTE), "1 h 0 min");
+        assert_eq!(format_duration(125.4 * MINUTE), "2 h 5 min");
+        assert_eq!(format_duration(-MINUTE), "0 min");
+    }
+}
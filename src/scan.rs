@@ -0,0 +1,559 @@
+extern crate web_sys;
+
+use js_sys::{Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Node, Range, TreeWalker};
+
+/// Id of the element the current chapter is rendered into.
+pub const READER_CONTENT_ID: &str = "reader-content";
+
+// NodeFilter.SHOW_TEXT
+const SHOW_TEXT: u32 = 0x4;
+// Node.TEXT_NODE
+const TEXT_NODE: u16 = 3;
+
+const SENTENCE_END: [char; 7] = ['。', '！', '？', '!', '?', '\n', '」'];
+
+/// A position inside a text node.
+/// Like with DOM ranges, the offset is counted in UTF-16 code units.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TextPosition {
+    pub node: Node,
+    pub offset: u32,
+}
+
+/// Text scanned for a lookup together with where it starts.
+#[derive(Clone)]
+pub struct Scan {
+    pub text: String,
+    pub start: TextPosition,
+    pub root_id: &'static str,
+}
+
+impl Scan {
+    /// Range covering the first `chars` characters of the scanned text.
+    pub fn range(&self, chars: usize) -> Option<Range> {
+        let root = root_by_id(self.root_id)?;
+        let end = advance(&root, &self.start, chars)?;
+        range_between(&self.start, &end)
+    }
+
+    /// Character offset of the scanned text within its root, not counting furigana.
+    pub fn offset(&self) -> Option<usize> {
+        offset_of(self.root_id, &self.start)
+    }
+
+    /// The sentence the scanned text starts in.
+    pub fn sentence(&self) -> Option<String> {
+        let text = root_text(self.root_id)?;
+        let offset = self.offset()?;
+
+        let chars = text.chars().collect::<Vec<_>>();
+        let (start, end) = sentence_bounds(&chars, offset);
+
+        Some(
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        )
+    }
+}
+
+/// Text the user selected, counted in characters like `Scan::offset`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SelectedText {
+    pub offset: usize,
+    pub len: usize,
+    pub text: String,
+}
+
+/// Start and end of every sentence in `chars`, as character indices.
+pub fn sentence_spans(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in chars.iter().enumerate() {
+        if SENTENCE_END.contains(c) {
+            spans.push((start, i + 1));
+            start = i + 1;
+        }
+    }
+    if start < chars.len() {
+        spans.push((start, chars.len()));
+    }
+
+    spans
+}
+
+/// Start and end of the sentence around the character `offset`, as character indices.
+pub fn sentence_bounds(chars: &[char], offset: usize) -> (usize, usize) {
+    let offset = offset.min(chars.len());
+
+    let start = chars[..offset]
+        .iter()
+        .rposition(|c| SENTENCE_END.contains(c))
+        .map_or(0, |i| i + 1);
+    let end = chars[offset..]
+        .iter()
+        .position(|c| SENTENCE_END.contains(c))
+        .map_or(chars.len(), |i| offset + i + 1);
+
+    (start, end)
+}
+
+pub(crate) fn root_by_id(id: &str) -> Option<Node> {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    document.get_element_by_id(id).map(Node::from)
+}
+
+// Furigana, scripts and styles are not part of the text, so we never scan or count them
+fn is_hidden(node: &Node) -> bool {
+    node.parent_element()
+        .and_then(|element| element.closest("rt, rp, script, style").ok().flatten())
+        .is_some()
+}
+
+/// Iterates the text nodes of a root in document order, skipping furigana, scripts and styles.
+struct TextNodes {
+    walker: TreeWalker,
+    first: Option<Node>,
+}
+
+impl Iterator for TextNodes {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(node) = self.first.take() {
+            return Some(node);
+        }
+
+        loop {
+            let node = self.walker.next_node().ok().flatten()?;
+            if !is_hidden(&node) {
+                return Some(node);
+            }
+        }
+    }
+}
+
+fn text_nodes_after(root: &Node, node: &Node) -> Option<TextNodes> {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let walker = document
+        .create_tree_walker_with_what_to_show(root, SHOW_TEXT)
+        .ok()?;
+    walker.set_current_node(node);
+
+    Some(TextNodes {
+        walker,
+        first: None,
+    })
+}
+
+fn text_nodes_from(root: &Node, node: &Node) -> Option<TextNodes> {
+    let mut nodes = text_nodes_after(root, node)?;
+    nodes.first = Some(node.clone());
+
+    Some(nodes)
+}
+
+fn char_index(text: &str, utf16_offset: u32) -> usize {
+    let target = usize::try_from(utf16_offset).unwrap_or(usize::MAX);
+    let mut units = 0;
+
+    text.chars()
+        .take_while(|c| {
+            let before = units < target;
+            units += c.len_utf16();
+            before
+        })
+        .count()
+}
+
+fn utf16_offset(text: &str, char_index: usize) -> u32 {
+    let units = text.chars().take(char_index).map(char::len_utf16).sum();
+    u32::try_from(units).unwrap_or(u32::MAX)
+}
+
+/// Finds the first text position at or after the given DOM position.
+pub(crate) fn first_text_at(root: &Node, node: &Node, offset: u32) -> Option<TextPosition> {
+    if node.node_type() == TEXT_NODE {
+        if !is_hidden(node) {
+            return Some(TextPosition {
+                node: node.clone(),
+                offset,
+            });
+        }
+
+        return text_nodes_after(root, node)?
+            .next()
+            .map(|node| TextPosition { node, offset: 0 });
+    }
+
+    // For elements, the offset is the index of the child node after the caret
+    let Some(child) = node.child_nodes().item(offset) else {
+        return text_nodes_after(root, node)?
+            .next()
+            .map(|node| TextPosition { node, offset: 0 });
+    };
+
+    if child.node_type() == TEXT_NODE && !is_hidden(&child) {
+        return Some(TextPosition {
+            node: child,
+            offset: 0,
+        });
+    }
+
+    text_nodes_after(root, &child)?
+        .next()
+        .map(|node| TextPosition { node, offset: 0 })
+}
+
+/// Reads up to `max_chars` characters of text starting at `start`.
+fn read_forward(root: &Node, start: &TextPosition, max_chars: usize) -> String {
+    let Some(nodes) = text_nodes_from(root, &start.node) else {
+        return String::new();
+    };
+
+    let mut text = String::new();
+    let mut count = 0;
+    let mut offset = start.offset;
+
+    for node in nodes {
+        let data = node.text_content().unwrap_or_default();
+        let skip = char_index(&data, offset);
+        offset = 0;
+
+        for c in data.chars().skip(skip).take(max_chars - count) {
+            text.push(c);
+            count += 1;
+        }
+
+        if count >= max_chars {
+            break;
+        }
+    }
+
+    text
+}
+
+/// Finds the position `chars` characters after `start`.
+/// Stops at the end of the root if it has fewer characters left.
+fn advance(root: &Node, start: &TextPosition, chars: usize) -> Option<TextPosition> {
+    let mut remaining = chars;
+    let mut offset = start.offset;
+    let mut last = None;
+
+    for node in text_nodes_from(root, &start.node)? {
+        let data = node.text_content().unwrap_or_default();
+        let skip = char_index(&data, offset);
+        let available = data.chars().count().saturating_sub(skip);
+        offset = 0;
+
+        if remaining <= available {
+            let offset = utf16_offset(&data, skip + remaining);
+            return Some(TextPosition { node, offset });
+        }
+
+        remaining -= available;
+        let offset = utf16_offset(&data, usize::MAX);
+        last = Some(TextPosition { node, offset });
+    }
+
+    last
+}
+
+/// The text of the element `root_id`, not including furigana.
+pub fn root_text(root_id: &str) -> Option<String> {
+    let root = root_by_id(root_id)?;
+    let first = text_nodes_after(&root, &root)?.next()?;
+
+    Some(
+        text_nodes_from(&root, &first)?
+            .map(|node| node.text_content().unwrap_or_default())
+            .collect(),
+    )
+}
+
+/// Counts the characters in the element `root_id` before `position`, not counting furigana.
+pub fn offset_of(root_id: &str, position: &TextPosition) -> Option<usize> {
+    let root = root_by_id(root_id)?;
+    let first = text_nodes_after(&root, &root)?.next()?;
+
+    let mut offset = 0;
+    for node in text_nodes_from(&root, &first)? {
+        let data = node.text_content().unwrap_or_default();
+        if node == position.node {
+            return Some(offset + char_index(&data, position.offset));
+        }
+
+        offset += data.chars().count();
+    }
+
+    None
+}
+
+/// Finds the position `offset` characters into the element `root_id`, not counting furigana.
+pub fn position_at(root_id: &str, offset: usize) -> Option<TextPosition> {
+    let root = root_by_id(root_id)?;
+    let node = text_nodes_after(&root, &root)?.next()?;
+
+    advance(&root, &TextPosition { node, offset: 0 }, offset)
+}
+
+/// Range covering `chars` characters from `offset` in the element `root_id`, not counting furigana.
+pub fn offset_range(root_id: &str, offset: usize, chars: usize) -> Option<Range> {
+    let root = root_by_id(root_id)?;
+    let start = position_at(root_id, offset)?;
+    let end = advance(&root, &start, chars)?;
+
+    range_between(&start, &end)
+}
+
+/// Ranges covering the given spans of (offset, chars) in the element `root_id`, not counting furigana.
+/// Unlike calling `offset_range` for each, this walks the text only once.
+pub fn offset_ranges(root_id: &str, spans: &[(usize, usize)]) -> Vec<Range> {
+    let mut boundaries = spans
+        .iter()
+        .flat_map(|&(offset, chars)| [offset, offset + chars])
+        .collect::<Vec<_>>();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let Some(root) = root_by_id(root_id) else {
+        return Vec::new();
+    };
+    let Some(nodes) = text_nodes_after(&root, &root) else {
+        return Vec::new();
+    };
+
+    let mut positions = Vec::with_capacity(boundaries.len());
+    let mut remaining = boundaries.iter().copied().peekable();
+    let mut base = 0;
+    for node in nodes {
+        let data = node.text_content().unwrap_or_default();
+        let len = data.chars().count();
+
+        while let Some(offset) = remaining.next_if(|&offset| offset <= base + len) {
+            positions.push(TextPosition {
+                node: node.clone(),
+                offset: utf16_offset(&data, offset - base),
+            });
+        }
+
+        base += len;
+    }
+
+    spans
+        .iter()
+        .filter_map(|&(offset, chars)| {
+            let start = positions.get(boundaries.binary_search(&offset).ok()?)?;
+            let end = positions.get(boundaries.binary_search(&(offset + chars)).ok()?)?;
+            range_between(start, end)
+        })
+        .collect()
+}
+
+/// Character offset of the first text in the element `root_id` that is visible at the top
+/// of the scrolling element `container_id`, not counting furigana.
+pub fn first_visible_offset(root_id: &str, container_id: &str) -> Option<usize> {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let root = root_by_id(root_id)?;
+    let top = document
+        .get_element_by_id(container_id)?
+        .get_bounding_client_rect()
+        .top();
+    let range = document.create_range().ok()?;
+
+    let mut base = 0;
+    for node in text_nodes_after(&root, &root)? {
+        let data = node.text_content().unwrap_or_default();
+        let len = data.chars().count();
+
+        range.select_node_contents(&node).ok()?;
+        let rect = range.get_bounding_client_rect();
+
+        // Whitespace between blocks isn't laid out and has an empty rectangle
+        if rect.height() > 0.0 && rect.bottom() > top {
+            // Characters further into the node are further down, so the first visible one can be bisected
+            let (mut low, mut high) = (0, len);
+            while low < high {
+                let mid = (low + high) / 2;
+                range.set_start(&node, utf16_offset(&data, mid)).ok()?;
+                range.set_end(&node, utf16_offset(&data, mid + 1)).ok()?;
+
+                if range.get_bounding_client_rect().bottom() > top {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+
+            return Some(base + low);
+        }
+
+        base += len;
+    }
+
+    None
+}
+
+fn range_between(start: &TextPosition, end: &TextPosition) -> Option<Range> {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    let range = document.create_range().ok()?;
+    range.set_start(&start.node, start.offset).ok()?;
+    range.set_end(&end.node, end.offset).ok()?;
+
+    Some(range)
+}
+
+fn scan_at(root_id: &'static str, node: &Node, offset: u32, max_chars: usize) -> Option<Scan> {
+    let root = root_by_id(root_id)?;
+    if !root.contains(Some(node)) {
+        return None;
+    }
+
+    let start = first_text_at(&root, node, offset)?;
+
+    let text = read_forward(&root, &start, max_chars)
+        .chars()
+        .take_while(|c| !SENTENCE_END.contains(c))
+        .collect::<String>();
+
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    Some(Scan {
+        text,
+        start,
+        root_id,
+    })
+}
+
+/// Scans the text following the caret the user placed inside the element `root_id`.
+/// The user's selection itself is left untouched.
+pub fn scan_selection(root_id: &'static str, max_chars: usize) -> Option<Scan> {
+    let window = web_sys::window().expect("should have window");
+
+    // Selection in eg iframe or otherwise inaccessible
+    let selection = window.get_selection().expect("Should have selection")?;
+
+    // We want to allow user range selection
+    if selection.type_() != "Caret" {
+        return None;
+    }
+
+    scan_at(
+        root_id,
+        &selection.anchor_node()?,
+        selection.anchor_offset(),
+        max_chars,
+    )
+}
+
+/// The text the user selected inside the element `root_id`, if any.
+pub fn selected_text(root_id: &str) -> Option<SelectedText> {
+    let window = web_sys::window().expect("should have window");
+    let selection = window.get_selection().expect("Should have selection")?;
+
+    if selection.type_() != "Range" {
+        return None;
+    }
+
+    let range = selection.get_range_at(0).ok()?;
+    let root = root_by_id(root_id)?;
+    let start_node = range.start_container().ok()?;
+    let end_node = range.end_container().ok()?;
+    if !root.contains(Some(&start_node)) || !root.contains(Some(&end_node)) {
+        return None;
+    }
+
+    let start = first_text_at(&root, &start_node, range.start_offset().ok()?)?;
+    let end = first_text_at(&root, &end_node, range.end_offset().ok()?)?;
+    let offset = offset_of(root_id, &start)?;
+    let len = offset_of(root_id, &end)?.checked_sub(offset)?;
+
+    let text = root_text(root_id)?
+        .chars()
+        .skip(offset)
+        .take(len)
+        .collect::<String>();
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    Some(SelectedText { offset, len, text })
+}
+
+fn caret_from_point(x: f32, y: f32) -> Option<(Node, u32)> {
+    let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+
+    // This is the standard way, but not every browser supports it yet
+    if Reflect::has(&document, &JsValue::from("caretPositionFromPoint")).unwrap_or(false) {
+        let caret = document.caret_position_from_point(x, y)?;
+        return Some((caret.offset_node()?, caret.offset()));
+    }
+
+    // Otherwise fall back to the older WebKit API web_sys has no bindings for
+    let function = Reflect::get(&document, &JsValue::from("caretRangeFromPoint"))
+        .ok()?
+        .dyn_into::<Function>()
+        .ok()?;
+    let range = function
+        .call2(&document, &JsValue::from(x), &JsValue::from(y))
+        .ok()?
+        .dyn_into::<Range>()
+        .ok()?;
+
+    Some((range.start_container().ok()?, range.start_offset().ok()?))
+}
+
+/// Scans the text following the client coordinates `x` and `y`, eg under the mouse.
+#[allow(clippy::cast_possible_truncation)]
+pub fn scan_point(root_id: &'static str, x: f64, y: f64, max_chars: usize) -> Option<Scan> {
+    let (node, offset) = caret_from_point(x as f32, y as f32)?;
+
+    scan_at(root_id, &node, offset, max_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn splits_sentences() {
+        let text = chars("雨だ。行こう！「本当」そう");
+        assert_eq!(sentence_spans(&text), [(0, 3), (3, 7), (7, 11), (11, 13)]);
+    }
+
+    #[test]
+    fn splits_lines() {
+        assert_eq!(sentence_spans(&chars("一\n二")), [(0, 2), (2, 3)]);
+        assert!(sentence_spans(&[]).is_empty());
+    }
+
+    #[test]
+    fn finds_sentence_bounds() {
+        let text = chars("雨だ。行こう！そう");
+        assert_eq!(sentence_bounds(&text, 0), (0, 3));
+        assert_eq!(sentence_bounds(&text, 4), (3, 7));
+        assert_eq!(sentence_bounds(&text, 8), (7, 9));
+        // Offsets past the end belong to the last sentence
+        assert_eq!(sentence_bounds(&text, 20), (7, 9));
+    }
+}
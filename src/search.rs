@@ -0,0 +1,169 @@
+use std::rc::Rc;
+
+use dioxus::prelude::*;
+
+use crate::{
+    definitions::{get_terms, is_current, start_lookup, Lookup},
+    dict_index, highlight, history, kana,
+    read_state::ReaderState,
+};
+
+const WILDCARD_LIMIT: usize = 50;
+const REVERSE_LIMIT: usize = 30;
+
+async fn find_entries(
+    query: &str,
+    reverse: bool,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+) -> Result<Vec<yomi_dict::DictEntries>, String> {
+    if !reverse && !query.contains('*') {
+        return get_terms(query, reasons, db)
+            .await
+            .map_err(|e| e.to_string());
+    }
+
+    let expressions = if reverse {
+        dict_index::find_reverse(query, REVERSE_LIMIT).await
+    } else {
+        dict_index::find_wildcard(query, WILDCARD_LIMIT).await
+    }
+    .map_err(|e| e.to_string())?;
+
+    // Only keep the terms themselves, not whatever else matches their beginning
+    let mut entries = Vec::new();
+    for expression in expressions {
+        let found = get_terms(&expression, reasons, db)
+            .await
+            .map_err(|e| e.to_string())?;
+        entries.extend(found.into_iter().filter(|d| d.expression == expression));
+    }
+
+    Ok(entries)
+}
+
+/// Searches for `query` and shows the results, returning whether anything was found.
+async fn search(
+    query: &str,
+    reverse: bool,
+    lookups: &UseRef<Vec<Lookup>>,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &yomi_dict::Reasons,
+) -> bool {
+    if db.read().is_none() {
+        log::error!("Cannot search since DB is not loaded yet!");
+        return false;
+    }
+
+    let generation = start_lookup();
+    let entries = match find_entries(query, reverse, db, reasons).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Cannot search for {query} due to error {e}");
+            return false;
+        }
+    };
+
+    if !is_current(generation) {
+        log::info!("Discarding stale search for {query}");
+        return false;
+    }
+
+    // The results are no longer about the word in the book
+    highlight::clear(highlight::MATCH);
+
+    let found = !entries.is_empty();
+    lookups.set(vec![Lookup {
+        text: query.to_string(),
+        entries: Rc::new(entries),
+    }]);
+
+    found
+}
+
+#[derive(Props)]
+pub struct SearchProps<'a> {
+    lookups: &'a UseRef<Vec<Lookup>>,
+    db: &'a UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &'a UseState<yomi_dict::Reasons>,
+    read_state: &'a UseRef<Option<ReaderState>>,
+}
+
+pub fn search_component<'a>(cx: Scope<'a, SearchProps<'a>>) -> Element<'a> {
+    let query = use_state(cx, String::new);
+    // Searches English glossaries instead of Japanese expressions
+    let reverse = use_state(cx, || false);
+
+    let placeholder = if *reverse.get() {
+        "Search definitions in English"
+    } else {
+        "Search in romaji, kana or kanji. Use * as wildcard"
+    };
+
+    cx.render(rsx! {
+        form{
+            class: "flex gap-2 mt-2",
+
+            prevent_default: "onsubmit",
+            onsubmit: move |_| {
+                let reverse = *reverse.get();
+                let text = if reverse {
+                    query.get().trim().to_string()
+                } else {
+                    kana::romaji_to_kana(query.get().trim(), true)
+                };
+                query.set(text.clone());
+
+                if text.is_empty() {
+                    return;
+                }
+
+                let lookups = cx.props.lookups.clone();
+                let db = cx.props.db.clone();
+                let reasons = cx.props.reasons.clone();
+                let read_state = cx.props.read_state.clone();
+                wasm_bindgen_futures::spawn_local(async move{
+                    if search(&text, reverse, &lookups, &db, reasons.get()).await {
+                        // Searches aren't in the book, so there is no sentence or position
+                        history::record(&lookups, &read_state, text, String::new(), None).await;
+                    }
+                });
+            },
+
+            input{
+                class: "flex-1 min-w-0 px-2 rounded border-2",
+
+                r#type: "search",
+                placeholder: "{placeholder}",
+                value: "{query}",
+                oninput: move |evt: FormEvent| {
+                    if *reverse.get() {
+                        query.set(evt.value.clone());
+                    } else {
+                        query.set(kana::romaji_to_kana(&evt.value, false));
+                    }
+                },
+            }
+
+            label{
+                class: "flex items-center gap-1 text-sm",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{reverse}",
+                    onchange: move |evt: FormEvent| reverse.set(evt.value == "true"),
+                }
+
+                "English"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                r#type: "submit",
+
+                "Search"
+            }
+        }
+    })
+}
@@ -0,0 +1,91 @@
+use dioxus::prelude::*;
+
+use crate::definitions::get_terms;
+
+// Longest text we try to match a single word against
+const MAX_WORD_LEN: usize = 12;
+
+/// A word found in some text, with its position counted in characters.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    pub offset: usize,
+    pub len: usize,
+    pub expression: String,
+    pub reading: String,
+}
+
+/// Whether `c` can be part of a Japanese word, so there is any point in looking it up.
+pub fn is_japanese(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // Kana
+        | '\u{3400}'..='\u{4dbf}' // Kanji extension A
+        | '\u{4e00}'..='\u{9fff}' // Kanji
+        | '\u{f900}'..='\u{faff}' // Compatibility kanji
+        | '\u{ff66}'..='\u{ff9f}' // Half-width katakana
+        | '々'
+    )
+}
+
+fn is_hiragana(c: char) -> bool {
+    ('\u{3041}'..='\u{309f}').contains(&c)
+}
+
+/// Splits `text` into the longest dictionary words from left to right,
+/// calling `progress` with the number of characters processed so far.
+///
+/// Single hiragana like particles are left out, since they are grammar rather than vocabulary.
+pub async fn segment(
+    text: &str,
+    reasons: &yomi_dict::Reasons,
+    db: &UseRef<Option<yomi_dict::IndexedDB>>,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Vec<Token>, yomi_dict::YomiDictError> {
+    const PROGRESS_STEP: usize = 500;
+
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut next_progress = 0;
+
+    let mut i = 0;
+    while i < chars.len() {
+        if i >= next_progress {
+            progress(i, chars.len());
+            next_progress = i + PROGRESS_STEP;
+        }
+
+        if !is_japanese(chars[i]) {
+            i += 1;
+            continue;
+        }
+
+        let window = chars[i..]
+            .iter()
+            .take(MAX_WORD_LEN)
+            .take_while(|c| is_japanese(**c))
+            .collect::<String>();
+
+        let found = get_terms(&window, reasons, db).await?;
+        let best = found.first().and_then(|d| {
+            let len = d.entries.iter().map(|e| e.source_len).max()?;
+            Some((d, len))
+        });
+
+        match best {
+            Some((_, 1)) if is_hiragana(chars[i]) => i += 1,
+            Some((d, len)) if len > 0 => {
+                tokens.push(Token {
+                    offset: i,
+                    len,
+                    expression: d.expression.clone(),
+                    reading: d.reading.clone(),
+                });
+                i += len;
+            }
+            _ => i += 1,
+        }
+    }
+
+    progress(chars.len(), chars.len());
+
+    Ok(tokens)
+}
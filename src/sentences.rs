@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{
+    analysis::{self, AnalysisState, BookAnalysis},
+    mining,
+    read_state::ReaderState,
+    scan,
+    segment::Token,
+};
+
+// Rendering thousands of sentences at once makes the panel sluggish
+const PAGE_SIZE: usize = 50;
+
+/// A sentence with exactly one unknown word, which makes a good flash card.
+pub struct Candidate<'a> {
+    pub page: usize,
+    /// Sentence text before, of and after the unknown word
+    pub before: String,
+    pub target: String,
+    pub after: String,
+    pub word: &'a Token,
+}
+
+impl Candidate<'_> {
+    pub fn sentence(&self) -> String {
+        format!("{}{}{}", self.before, self.target, self.after)
+    }
+}
+
+/// Finds the sentences of the book with exactly one unknown word, in reading order.
+/// The unknown word may occur more than once in the sentence.
+pub fn find<'a>(analysis: &'a BookAnalysis, known: &HashSet<String>) -> Vec<Candidate<'a>> {
+    let mut candidates = Vec::new();
+
+    for chapter in &analysis.chapters {
+        // Both are sorted by offset, so each token is only looked at once
+        let mut tokens = chapter.tokens.iter().peekable();
+
+        for (start, end) in scan::sentence_spans(&chapter.chars) {
+            let mut unknown: Option<&Token> = None;
+            let mut single = true;
+
+            while let Some(token) = tokens.next_if(|t| t.offset < end) {
+                if known.contains(&token.expression) {
+                    continue;
+                }
+                match unknown {
+                    None => unknown = Some(token),
+                    Some(first) if first.expression == token.expression => {}
+                    Some(_) => single = false,
+                }
+            }
+
+            let Some(word) = unknown.filter(|_| single) else {
+                continue;
+            };
+            let word_end = (word.offset + word.len).min(end);
+
+            let text = |from: usize, to: usize| chapter.chars[from..to].iter().collect::<String>();
+            candidates.push(Candidate {
+                page: chapter.page,
+                before: text(start, word.offset).trim_start().to_string(),
+                target: text(word.offset, word_end),
+                after: text(word_end, end).trim_end().to_string(),
+                word,
+            });
+        }
+    }
+
+    candidates
+}
+
+#[derive(Props)]
+pub struct SentencesProps<'a> {
+    analysis: &'a UseRef<AnalysisState>,
+    read_state: &'a UseRef<Option<ReaderState>>,
+    db: &'a UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &'a UseState<yomi_dict::Reasons>,
+    known: &'a UseRef<HashSet<String>>,
+}
+
+pub fn sentences_component<'a>(cx: Scope<'a, SentencesProps<'a>>) -> Element<'a> {
+    let analysis = cx.props.analysis;
+    let read_state = cx.props.read_state;
+    let known = cx.props.known;
+
+    // Many sentences share the same unknown word, one is usually enough to learn it
+    let one_per_word = use_state(cx, || true);
+    let shown = use_state(cx, || PAGE_SIZE);
+    let mined = use_ref(cx, HashSet::<String>::new);
+
+    let current_book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let start = move |_: MouseEvent| {
+        analysis::start(read_state, cx.props.db, cx.props.reasons, known, analysis);
+    };
+
+    let state = analysis.read();
+    let result = match &*state {
+        AnalysisState::Running(current, total) => {
+            let current = current + 1;
+            return cx.render(rsx!(p{
+                class: "mt-2",
+
+                "Analyzing chapter {current}/{total}"
+            }));
+        }
+        AnalysisState::Done(result) if Some(&result.book) == current_book.as_ref() => result,
+        AnalysisState::Done(_) | AnalysisState::Idle => {
+            return cx.render(rsx!(div{
+                class: "flex flex-col gap-2 mt-2",
+
+                p{ "Find sentences in this book with exactly one unknown word, to mine them as sentence cards." }
+                button{
+                    class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                    onclick: start,
+
+                    "Analyze book"
+                }
+            }));
+        }
+    };
+
+    let mut candidates = find(result, &known.read());
+    if *one_per_word.get() {
+        let mut seen = HashSet::new();
+        candidates.retain(|c| seen.insert(c.word.expression.as_str()));
+    }
+    let total = candidates.len();
+    let more = total > *shown.get();
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-wrap items-center gap-2 mt-2",
+
+            p{
+                class: "flex-1 text-sm text-gray-600",
+
+                "{total} sentences with one unknown word"
+            }
+
+            label{
+                class: "flex items-center gap-1 text-sm",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{one_per_word}",
+                    onchange: move |evt: FormEvent| {
+                        one_per_word.set(evt.value == "true");
+                        shown.set(PAGE_SIZE);
+                    },
+                }
+
+                "One per word"
+            }
+        }
+
+        ul{
+            class: "list-none",
+
+            candidates.into_iter().take(*shown.get()).map(|c| {
+                let (page, offset) = (c.page, c.word.offset);
+                let chapter_title = result.chapter(page).map_or("", |c| c.title.as_str());
+                let sentence = c.sentence();
+                let key = format!("{page}-{offset}");
+                let is_mined = mined.read().contains(&key);
+                let mined_key = key.clone();
+                let word = (c.word.expression.clone(), c.word.reading.clone(), sentence, result.book.clone());
+
+                rsx!(li{
+                    key: "{key}",
+                    class: "border-b py-1",
+
+                    p{
+                        "{c.before}"
+                        mark{
+                            class: "bg-yellow-200 rounded",
+
+                            "{c.target}"
+                        }
+                        "{c.after}"
+                    }
+
+                    span{
+                        class: "text-sm text-gray-600 mr-2",
+
+                        "{c.word.expression}【{c.word.reading}】 in {chapter_title}"
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                        onclick: move |_| read_state.with_mut(|state| {
+                            if let Some(state) = state.as_mut() {
+                                state.jump_to(page, Some(offset));
+                            }
+                        }),
+
+                        "Go to"
+                    }
+
+                    button{
+                        class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300 disabled:opacity-50",
+                        disabled: "{is_mined}",
+
+                        onclick: move |_| {
+                            mined.with_mut(|m| m.insert(mined_key.clone()));
+
+                            let db = cx.props.db.clone();
+                            let reasons = cx.props.reasons.clone();
+                            let (expression, reading, sentence, book) = word.clone();
+                            wasm_bindgen_futures::spawn_local(async move {
+                                mining::mine_word(expression, reading, sentence, book, &db, reasons.get()).await;
+                            });
+                        },
+
+                        "Mine"
+                    }
+                })
+            })
+        }
+
+        more.then(|| rsx!(button{
+            class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded my-2",
+
+            onclick: move |_| shown.modify(|s| s + PAGE_SIZE),
+
+            "Show more ({total})"
+        }))
+    })
+}
@@ -0,0 +1,302 @@
+extern crate web_sys;
+
+use dioxus::prelude::*;
+
+use crate::normalize::Normalization;
+
+// Share of the book in the split layout, in percent
+pub const MIN_SPLIT: f64 = 20.0;
+pub const MAX_SPLIT: f64 = 90.0;
+const DEFAULT_SPLIT: f64 = 60.0;
+
+/// How lookups are triggered in the reader besides clicking.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    Click,
+    Shift,
+    Hover,
+}
+
+impl ScanMode {
+    pub const ALL: [Self; 3] = [Self::Click, Self::Shift, Self::Hover];
+
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Click => "click",
+            Self::Shift => "shift",
+            Self::Hover => "hover",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Click => "Click only",
+            Self::Shift => "Hold Shift over text",
+            Self::Hover => "Hover over text",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    /// How long the mouse has to rest before a hover lookup is started.
+    pub const fn hover_delay(self) -> i32 {
+        match self {
+            Self::Click | Self::Shift => 50,
+            Self::Hover => 400,
+        }
+    }
+}
+
+/// Where definitions are shown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Split,
+    Popup,
+}
+
+impl Layout {
+    pub const ALL: [Self; 2] = [Self::Split, Self::Popup];
+
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Split => "split",
+            Self::Popup => "popup",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Split => "Panel below the book",
+            Self::Popup => "Popup next to the word",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layout| layout.key() == key)
+    }
+}
+
+/// User preferences, stored per device in local storage.
+#[derive(Clone, PartialEq)]
+pub struct Settings {
+    pub scan_mode: ScanMode,
+    pub layout: Layout,
+    /// Share of the book in the split layout, in percent
+    pub split: f64,
+    pub definitions_collapsed: bool,
+    pub normalization: Normalization,
+    pub highlight_unknown: bool,
+}
+
+fn storage() -> web_sys::Storage {
+    let window = web_sys::window().expect("should have window");
+    window
+        .local_storage()
+        .expect("should be able to get storage")
+        .expect("should have storage")
+}
+
+fn load_item(key: &str) -> Option<String> {
+    storage()
+        .get_item(key)
+        .expect("Should be able to access storage")
+}
+
+// TODO error checking
+fn save_item(key: &str, value: &str) {
+    storage().set_item(key, value).ok();
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let scan_mode = load_item("scan_mode")
+            .and_then(|s| ScanMode::from_key(&s))
+            .unwrap_or(ScanMode::Click);
+
+        let layout = load_item("layout")
+            .and_then(|s| Layout::from_key(&s))
+            .unwrap_or(Layout::Split);
+
+        let split = load_item("split")
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|split| split.is_finite())
+            .map_or(DEFAULT_SPLIT, |split| split.clamp(MIN_SPLIT, MAX_SPLIT));
+
+        let definitions_collapsed = load_item("definitions_collapsed").as_deref() == Some("true");
+
+        let default = Normalization::default();
+        let load_flag = |key, default| load_item(key).map_or(default, |s| s == "true");
+        let normalization = Normalization {
+            width: load_flag("normalize_width", default.width),
+            kana: load_flag("normalize_kana", default.kana),
+            iteration_marks: load_flag("normalize_iteration_marks", default.iteration_marks),
+            long_vowels: load_flag("normalize_long_vowels", default.long_vowels),
+        };
+
+        let highlight_unknown = load_item("highlight_unknown").as_deref() == Some("true");
+
+        Self {
+            scan_mode,
+            layout,
+            split,
+            definitions_collapsed,
+            normalization,
+            highlight_unknown,
+        }
+    }
+
+    pub fn save(&self) {
+        save_item("scan_mode", self.scan_mode.key());
+        save_item("layout", self.layout.key());
+        save_item("split", &self.split.to_string());
+        save_item(
+            "definitions_collapsed",
+            &self.definitions_collapsed.to_string(),
+        );
+        save_item("normalize_width", &self.normalization.width.to_string());
+        save_item("normalize_kana", &self.normalization.kana.to_string());
+        save_item(
+            "normalize_iteration_marks",
+            &self.normalization.iteration_marks.to_string(),
+        );
+        save_item(
+            "normalize_long_vowels",
+            &self.normalization.long_vowels.to_string(),
+        );
+        save_item("highlight_unknown", &self.highlight_unknown.to_string());
+    }
+}
+
+#[derive(Props)]
+pub struct SettingsProps<'a> {
+    settings: &'a UseRef<Settings>,
+}
+
+pub fn settings_component<'a>(cx: Scope<'a, SettingsProps<'a>>) -> Element<'a> {
+    let settings = cx.props.settings;
+
+    let scan_mode = settings.read().scan_mode.key();
+    let layout = settings.read().layout.key();
+    let normalization = settings.read().normalization;
+    let highlight_unknown = settings.read().highlight_unknown;
+
+    let options: [(&str, bool, fn(&mut Normalization) -> &mut bool); 4] = [
+        ("Character width", normalization.width, |n| &mut n.width),
+        ("Hiragana and katakana", normalization.kana, |n| &mut n.kana),
+        (
+            "Iteration marks (々, ゝ)",
+            normalization.iteration_marks,
+            |n| &mut n.iteration_marks,
+        ),
+        ("Long vowels (ー)", normalization.long_vowels, |n| {
+            &mut n.long_vowels
+        }),
+    ];
+
+    cx.render(rsx! {
+        details{
+            class: "relative m-2",
+
+            summary{
+                class: "list-none bg-gray-100 hover:bg-gray-200 py-2 px-4 rounded cursor-pointer",
+
+                "Settings"
+            }
+
+            div{
+                class: "absolute right-0 z-10 mt-1 p-2 w-64 flex flex-col gap-2 bg-white rounded-md border-2",
+
+                label{
+                    class: "flex flex-col",
+
+                    "Lookup"
+
+                    select{
+                        class: "bg-gray-100 rounded p-1",
+
+                        value: "{scan_mode}",
+                        onchange: move |evt: FormEvent| {
+                            if let Some(mode) = ScanMode::from_key(&evt.value) {
+                                settings.with_mut(|s| {
+                                    s.scan_mode = mode;
+                                    s.save();
+                                });
+                            }
+                        },
+
+                        ScanMode::ALL.into_iter().map(|mode| {
+                            let (key, label) = (mode.key(), mode.label());
+                            rsx!(option{ key: "{key}", value: "{key}", "{label}" })
+                        })
+                    }
+                }
+
+                label{
+                    class: "flex flex-col",
+
+                    "Definitions"
+
+                    select{
+                        class: "bg-gray-100 rounded p-1",
+
+                        value: "{layout}",
+                        onchange: move |evt: FormEvent| {
+                            if let Some(layout) = Layout::from_key(&evt.value) {
+                                settings.with_mut(|s| {
+                                    s.layout = layout;
+                                    s.save();
+                                });
+                            }
+                        },
+
+                        Layout::ALL.into_iter().map(|layout| {
+                            let (key, label) = (layout.key(), layout.label());
+                            rsx!(option{ key: "{key}", value: "{key}", "{label}" })
+                        })
+                    }
+                }
+
+                label{
+                    class: "flex items-center gap-1",
+
+                    input{
+                        r#type: "checkbox",
+                        checked: "{highlight_unknown}",
+                        onchange: move |evt: FormEvent| settings.with_mut(|s| {
+                            s.highlight_unknown = evt.value == "true";
+                            s.save();
+                        }),
+                    }
+
+                    "Underline unknown words"
+                }
+
+                fieldset{
+                    class: "flex flex-col",
+
+                    legend{ "Normalize before lookup" }
+
+                    options.into_iter().map(|(label, enabled, flag)| rsx!(
+                        label{
+                            key: "{label}",
+                            class: "flex items-center gap-1 text-sm",
+
+                            input{
+                                r#type: "checkbox",
+                                checked: "{enabled}",
+                                onchange: move |evt: FormEvent| settings.with_mut(|s| {
+                                    *flag(&mut s.normalization) = evt.value == "true";
+                                    s.save();
+                                }),
+                            }
+
+                            "{label}"
+                        }
+                    ))
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,430 @@
+use rexie::{Index, KeyRange, ObjectStore, Rexie, TransactionMode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+const HISTORY: &str = "history";
+const MINING: &str = "mining";
+const KNOWN_WORDS: &str = "known_words";
+const BOOK_PROFILES: &str = "book_profiles";
+const BOOKMARKS: &str = "bookmarks";
+const ANNOTATIONS: &str = "annotations";
+const READING_SESSIONS: &str = "reading_sessions";
+const READING_GOAL: &str = "reading_goal";
+
+// There is only one goal, stored under this key
+const GOAL_KEY: &str = "daily";
+
+/// A word the user looked up while reading.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub expression: String,
+    pub reading: String,
+    pub glossary: Vec<String>,
+    /// The scanned text the lookup started from
+    pub text: String,
+    pub sentence: String,
+    pub book: String,
+    pub chapter: usize,
+    /// Character offset of the scanned text in the chapter
+    pub offset: Option<usize>,
+    /// Milliseconds since the epoch
+    pub timestamp: f64,
+}
+
+/// A word the user wants to study, eg by turning it into a flash card.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct MiningEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub expression: String,
+    pub reading: String,
+    pub glossary: Vec<String>,
+    pub sentence: String,
+    pub book: String,
+    pub timestamp: f64,
+}
+
+/// A word the user already knows, so it needn't be studied or highlighted.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct KnownWord {
+    pub expression: String,
+    pub reading: String,
+    pub timestamp: f64,
+}
+
+/// How hard some text is to read for the user.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    /// Share of word occurrences the user knows, in percent
+    pub known_coverage: f64,
+    /// Share of word occurrences that are rare according to the frequency dictionaries, in percent.
+    /// Words the dictionaries don't rank are left out, and it is None if they rank none.
+    pub rare_share: Option<f64>,
+    pub unique_kanji: usize,
+    /// Average sentence length in characters
+    pub sentence_length: f64,
+    /// New unknown words per page, which are likely to be looked up
+    pub lookups_per_page: f64,
+}
+
+/// Summary of an analysed book, so it can be listed without being open.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct BookProfile {
+    pub book: String,
+    pub chapters: usize,
+    pub characters: usize,
+    pub difficulty: Difficulty,
+    pub timestamp: f64,
+}
+
+/// A place in a book the user wants to come back to.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Bookmark {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub name: String,
+    pub note: String,
+    pub book: String,
+    pub chapter: usize,
+    /// Character offset in the chapter, which unlike a scroll position doesn't depend on the layout
+    pub offset: usize,
+    /// The same position as an EPUB CFI, which other reading systems understand too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfi: Option<String>,
+    /// The text at the offset, to recognize the place by
+    pub text: String,
+    pub timestamp: f64,
+}
+
+/// Colour an annotation is highlighted in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    Yellow,
+    Green,
+    Blue,
+    Pink,
+}
+
+/// Highlighted text in a book, optionally with a note.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Annotation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub book: String,
+    pub chapter: usize,
+    /// Character offset and length of the highlighted text in the chapter
+    pub offset: usize,
+    pub len: usize,
+    pub text: String,
+    pub color: Color,
+    pub note: String,
+    pub timestamp: f64,
+}
+
+/// A stretch of reading a book, without the pauses in between.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReadingSession {
+    pub book: String,
+    /// Milliseconds since the epoch when reading started, which also identifies the session
+    pub start: f64,
+    /// Milliseconds since the epoch of the last activity
+    pub end: f64,
+    /// Milliseconds spent actively reading
+    pub active: f64,
+    /// Characters read moving forward through the book, without furigana
+    pub characters: usize,
+}
+
+/// What a daily goal is counted in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GoalUnit {
+    Characters,
+    Minutes,
+}
+
+/// How much the user wants to read each day.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DailyGoal {
+    pub amount: u32,
+    pub unit: GoalUnit,
+}
+
+async fn get_user_db() -> rexie::Result<Rexie> {
+    Rexie::builder("UserDatabase")
+        .version(7)
+        .add_object_store(
+            ObjectStore::new(HISTORY)
+                .key_path("id")
+                .auto_increment(true)
+                .add_index(Index::new("book", "book"))
+                .add_index(Index::new("timestamp", "timestamp")),
+        )
+        .add_object_store(ObjectStore::new(MINING).key_path("id").auto_increment(true))
+        .add_object_store(ObjectStore::new(KNOWN_WORDS).key_path("expression"))
+        .add_object_store(ObjectStore::new(BOOK_PROFILES).key_path("book"))
+        .add_object_store(
+            ObjectStore::new(BOOKMARKS)
+                .key_path("id")
+                .auto_increment(true)
+                .add_index(Index::new("book", "book")),
+        )
+        .add_object_store(
+            ObjectStore::new(ANNOTATIONS)
+                .key_path("id")
+                .auto_increment(true)
+                .add_index(Index::new("book", "book")),
+        )
+        .add_object_store(
+            ObjectStore::new(READING_SESSIONS)
+                .key_path("start")
+                .add_index(Index::new("book", "book")),
+        )
+        .add_object_store(ObjectStore::new(READING_GOAL))
+        .build()
+        .await
+}
+
+async fn add<T: Serialize>(store_name: &str, value: &T) -> Result<(), UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[store_name], TransactionMode::ReadWrite)?;
+    let store = transaction.store(store_name)?;
+
+    store
+        .add(&serde_wasm_bindgen::to_value(value)?, None)
+        .await?;
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+async fn get_all<T: serde::de::DeserializeOwned>(store_name: &str) -> Result<Vec<T>, UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[store_name], TransactionMode::ReadOnly)?;
+    let store = transaction.store(store_name)?;
+
+    store
+        .get_all(None, None, None, None)
+        .await?
+        .into_iter()
+        .map(|(_, value)| Ok(serde_wasm_bindgen::from_value(value)?))
+        .collect()
+}
+
+async fn get_by_book<T: serde::de::DeserializeOwned>(
+    store_name: &str,
+    book: &str,
+) -> Result<Vec<T>, UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[store_name], TransactionMode::ReadOnly)?;
+    let store = transaction.store(store_name)?;
+
+    let range = KeyRange::only(&JsValue::from(book))?;
+    store
+        .index("book")?
+        .get_all(Some(&range), None, None, None)
+        .await?
+        .into_iter()
+        .map(|(_, value)| Ok(serde_wasm_bindgen::from_value(value)?))
+        .collect()
+}
+
+async fn put<T: Serialize>(store_name: &str, value: &T) -> Result<(), UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[store_name], TransactionMode::ReadWrite)?;
+    let store = transaction.store(store_name)?;
+
+    store
+        .put(&serde_wasm_bindgen::to_value(value)?, None)
+        .await?;
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+async fn delete(store_name: &str, key: &JsValue) -> Result<(), UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[store_name], TransactionMode::ReadWrite)?;
+    let store = transaction.store(store_name)?;
+
+    store.delete(key).await?;
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+pub async fn add_history(entry: &HistoryEntry) -> Result<(), UserDbError> {
+    add(HISTORY, entry).await
+}
+
+/// All lookups, most recent first.
+pub async fn get_history() -> Result<Vec<HistoryEntry>, UserDbError> {
+    let mut history: Vec<HistoryEntry> = get_all(HISTORY).await?;
+    history.sort_by(|a, b| b.timestamp.total_cmp(&a.timestamp));
+
+    Ok(history)
+}
+
+pub async fn add_mining(entry: &MiningEntry) -> Result<(), UserDbError> {
+    add(MINING, entry).await
+}
+
+/// All mined words, most recent first.
+pub async fn get_mining() -> Result<Vec<MiningEntry>, UserDbError> {
+    let mut mining: Vec<MiningEntry> = get_all(MINING).await?;
+    mining.sort_by(|a, b| b.timestamp.total_cmp(&a.timestamp));
+
+    Ok(mining)
+}
+
+pub async fn delete_mining(id: u32) -> Result<(), UserDbError> {
+    delete(MINING, &id.into()).await
+}
+
+/// Adds known words, replacing any with the same expression.
+pub async fn add_known_words(words: &[KnownWord]) -> Result<(), UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[KNOWN_WORDS], TransactionMode::ReadWrite)?;
+    let store = transaction.store(KNOWN_WORDS)?;
+
+    for word in words {
+        store
+            .put(&serde_wasm_bindgen::to_value(word)?, None)
+            .await?;
+    }
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+pub async fn remove_known_word(expression: &str) -> Result<(), UserDbError> {
+    delete(KNOWN_WORDS, &expression.into()).await
+}
+
+pub async fn get_known_words() -> Result<Vec<KnownWord>, UserDbError> {
+    get_all(KNOWN_WORDS).await
+}
+
+pub async fn put_book_profile(profile: &BookProfile) -> Result<(), UserDbError> {
+    put(BOOK_PROFILES, profile).await
+}
+
+pub async fn get_book_profiles() -> Result<Vec<BookProfile>, UserDbError> {
+    get_all(BOOK_PROFILES).await
+}
+
+pub async fn add_bookmark(bookmark: &Bookmark) -> Result<(), UserDbError> {
+    add(BOOKMARKS, bookmark).await
+}
+
+/// The bookmarks of `book`, in reading order.
+pub async fn get_bookmarks(book: &str) -> Result<Vec<Bookmark>, UserDbError> {
+    let mut bookmarks: Vec<Bookmark> = get_by_book(BOOKMARKS, book).await?;
+    bookmarks.sort_by_key(|b| (b.chapter, b.offset));
+
+    Ok(bookmarks)
+}
+
+pub async fn delete_bookmark(id: u32) -> Result<(), UserDbError> {
+    delete(BOOKMARKS, &id.into()).await
+}
+
+pub async fn add_annotation(annotation: &Annotation) -> Result<(), UserDbError> {
+    add(ANNOTATIONS, annotation).await
+}
+
+/// Replaces the stored annotation with the same id, eg to change its note.
+pub async fn put_annotation(annotation: &Annotation) -> Result<(), UserDbError> {
+    put(ANNOTATIONS, annotation).await
+}
+
+/// The annotations of `book`, in reading order.
+pub async fn get_annotations(book: &str) -> Result<Vec<Annotation>, UserDbError> {
+    let mut annotations: Vec<Annotation> = get_by_book(ANNOTATIONS, book).await?;
+    annotations.sort_by_key(|a| (a.chapter, a.offset));
+
+    Ok(annotations)
+}
+
+pub async fn delete_annotation(id: u32) -> Result<(), UserDbError> {
+    delete(ANNOTATIONS, &id.into()).await
+}
+
+/// Saves a reading session, replacing an earlier save of the same session.
+pub async fn put_reading_session(session: &ReadingSession) -> Result<(), UserDbError> {
+    put(READING_SESSIONS, session).await
+}
+
+/// All reading sessions, oldest first.
+pub async fn get_reading_sessions() -> Result<Vec<ReadingSession>, UserDbError> {
+    let mut sessions: Vec<ReadingSession> = get_all(READING_SESSIONS).await?;
+    sessions.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    Ok(sessions)
+}
+
+/// Reading sessions started at `start` or later, oldest first.
+pub async fn get_reading_sessions_since(start: f64) -> Result<Vec<ReadingSession>, UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[READING_SESSIONS], TransactionMode::ReadOnly)?;
+    let store = transaction.store(READING_SESSIONS)?;
+
+    // Sessions are keyed by their start, so they come in order
+    let range = KeyRange::lower_bound(&JsValue::from_f64(start), false)?;
+    store
+        .get_all(Some(&range), None, None, None)
+        .await?
+        .into_iter()
+        .map(|(_, value)| Ok(serde_wasm_bindgen::from_value(value)?))
+        .collect()
+}
+
+/// Sets the daily reading goal, or removes it if None.
+pub async fn put_daily_goal(goal: Option<&DailyGoal>) -> Result<(), UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[READING_GOAL], TransactionMode::ReadWrite)?;
+    let store = transaction.store(READING_GOAL)?;
+
+    let key = JsValue::from(GOAL_KEY);
+    match goal {
+        Some(goal) => {
+            store
+                .put(&serde_wasm_bindgen::to_value(goal)?, Some(&key))
+                .await?;
+        }
+        None => store.delete(&key).await?,
+    }
+
+    transaction.done().await?;
+
+    Ok(())
+}
+
+pub async fn get_daily_goal() -> Result<Option<DailyGoal>, UserDbError> {
+    let db = get_user_db().await?;
+    let transaction = db.transaction(&[READING_GOAL], TransactionMode::ReadOnly)?;
+    let store = transaction.store(READING_GOAL)?;
+
+    let value = store.get(&JsValue::from(GOAL_KEY)).await?;
+    if value.is_undefined() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_wasm_bindgen::from_value(value)?))
+}
+
+#[derive(Error, Debug)]
+pub enum UserDbError {
+    #[error("An error occured with the rexie IndexedDB backend: `{0}`")]
+    Rexie(#[from] rexie::Error),
+    #[error("Error parsing JSObject: `{0}`")]
+    Jsobj(#[from] serde_wasm_bindgen::Error),
+}
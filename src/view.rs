@@ -1,50 +1,101 @@
 extern crate web_sys;
 
 use std::{
+    cell::{Cell, RefCell},
     collections::{HashMap, HashSet},
     path::Path,
+    rc::Rc,
 };
 
-use dioxus::prelude::*;
+use dioxus::{html::input_data::keyboard_types::Modifiers, prelude::*};
 use wasm_bindgen::{prelude::Closure, JsCast};
 
-use crate::read_state::ReaderState;
+use crate::{
+    highlight,
+    read_state::{PendingPosition, ReaderState},
+    scan::{self, Scan, SelectedText},
+    settings::ScanMode,
+};
 
 #[derive(Props)]
 pub struct ViewProps<'a> {
     read_state: &'a UseRef<Option<ReaderState>>,
-    onselect: EventHandler<'a, String>,
+    scan_mode: ScanMode,
+    onselect: EventHandler<'a, Scan>,
+    onhover: EventHandler<'a, Scan>,
+    /// Called with the text selected in the chapter, or None once nothing is selected anymore
+    onrangeselect: EventHandler<'a, Option<SelectedText>>,
 }
 
-fn clicked(onselect: &EventHandler<String>) {
-    const SELECTION_LENGTH: usize = 16;
-
-    let window = web_sys::window().expect("should have window");
+const SELECTION_LENGTH: usize = 16;
 
-    let selection = window.get_selection().expect("Should have selection");
+fn clicked(onselect: &EventHandler<Scan>, onrangeselect: &EventHandler<Option<SelectedText>>) {
+    onrangeselect.call(scan::selected_text(scan::READER_CONTENT_ID));
 
-    // Selection in eg iframe or otherwise inaccessible
-    let Some(selection) = selection else { return };
+    if let Some(scan) = scan::scan_selection(scan::READER_CONTENT_ID, SELECTION_LENGTH) {
+        log::info!("Clicked: {}", scan.text);
 
-    // We want to allow user range selection
-    if selection.type_() != "Caret" {
-        return;
+        onselect.call(scan);
     }
+}
 
-    if selection.modify("extend", "forward", "sentence").is_err() {
-        for _ in 0..SELECTION_LENGTH {
-            if selection.modify("extend", "forward", "character").is_err() {
-                break;
-            }
+fn hovered(
+    evt: &MouseEvent,
+    scan_mode: ScanMode,
+    onhover: &EventHandler<Scan>,
+    pointer: &UseRef<Option<(f64, f64)>>,
+) {
+    let point = evt.client_coordinates();
+    *pointer.write_silent() = Some((point.x, point.y));
+
+    let scanning = match scan_mode {
+        ScanMode::Click => false,
+        ScanMode::Shift => evt.modifiers().contains(Modifiers::SHIFT),
+        ScanMode::Hover => true,
+    };
+
+    if scanning {
+        if let Some(scan) =
+            scan::scan_point(scan::READER_CONTENT_ID, point.x, point.y, SELECTION_LENGTH)
+        {
+            onhover.call(scan);
         }
     }
+}
 
-    if let Some(sentence_end) = selection.to_string().as_string() {
-        let s = sentence_end.chars().take(SELECTION_LENGTH).collect();
-        log::info!("Clicked: {}", s);
+/// Pressing Shift scans where the pointer rests, as if it was moved with Shift held.
+fn enable_shift_callback(pointer: UseRef<Option<(f64, f64)>>) {
+    let window = web_sys::window().expect("should have window");
 
-        onselect.call(s);
-    }
+    let shift_callback =
+        Closure::<dyn Fn(web_sys::KeyboardEvent)>::new(move |evt: web_sys::KeyboardEvent| {
+            if evt.key() != "Shift" || evt.repeat() {
+                return;
+            }
+            let Some((x, y)) = *pointer.read() else {
+                return;
+            };
+            let Some(root) = scan::root_by_id(scan::READER_CONTENT_ID) else {
+                return;
+            };
+
+            // The move is handled like any other, so only the Shift scan mode scans
+            #[allow(clippy::cast_possible_truncation)]
+            let (x, y) = (x as i32, y as i32);
+            let mut init = web_sys::MouseEventInit::new();
+            init.client_x(x).client_y(y).shift_key(true).bubbles(true);
+            if let Ok(event) =
+                web_sys::MouseEvent::new_with_mouse_event_init_dict("mousemove", &init)
+            {
+                root.dispatch_event(&event).ok();
+            }
+        });
+
+    window
+        .add_event_listener_with_callback("keydown", shift_callback.as_ref().unchecked_ref())
+        .unwrap();
+
+    shift_callback.forget();
 }
 
 fn vec_to_blob(vec: &Vec<u8>) -> Option<web_sys::Blob> {
@@ -59,7 +110,9 @@ fn load_to_cache(
     read_state: &UseRef<Option<ReaderState>>,
 ) {
     read_state.with_mut(|state| {
-        let Some(state) = state.as_mut() else { return; };
+        let Some(state) = state.as_mut() else {
+            return;
+        };
 
         for resource in uncached_resources {
             let file = Path::new(resource.as_str());
@@ -110,7 +163,9 @@ fn process_text(
         let mut resource = resource;
         loop {
             // PathBuf::canonicalize would be better but isn't supported on this platform
-            let Some(rest) = resource.strip_prefix("../") else { break };
+            let Some(rest) = resource.strip_prefix("../") else {
+                break;
+            };
 
             resource = rest;
             path = path.parent().unwrap_or_else(|| Path::new("."));
@@ -152,17 +207,123 @@ fn process_text(
     })
 }
 
-fn apply_current_scroll(read_state: UseRef<Option<ReaderState>>) {
+/// Scrolls `range` to `context` pixels below the top of `element`, and returns the new scroll position.
+fn scroll_into_view(element: &web_sys::Element, range: &web_sys::Range, context: i32) -> i32 {
+    let top = range.get_bounding_client_rect().top() - element.get_bounding_client_rect().top();
+    #[allow(clippy::cast_possible_truncation)]
+    let scroll_top = (element.scroll_top() + top as i32 - context).max(0);
+
+    element.set_scroll_top(scroll_top);
+    scroll_top
+}
+
+// Images that fail to load change the layout too
+const IMAGE_EVENTS: [&str; 2] = ["load", "error"];
+
+/// Scrolls back to `range` whenever an image finishes loading and moves the text,
+/// unless the reader has scrolled away meanwhile.
+fn keep_in_view(element: web_sys::Element, range: web_sys::Range, context: i32, scroll_top: i32) {
     let window = web_sys::window().expect("should have window");
+    let document = window.document().expect("should have document");
+    let Some(images) = document
+        .get_element_by_id(scan::READER_CONTENT_ID)
+        .and_then(|root| root.query_selector_all("img").ok())
+    else {
+        return;
+    };
 
-    // If we apply the scroll position immediately, it will be reset to 0
-    // Instead, we push it to the end of the current event queue
+    let loading = (0..images.length())
+        .filter_map(|i| images.item(i))
+        .filter_map(|node| node.dyn_into::<web_sys::HtmlImageElement>().ok())
+        .filter(|image| !image.complete())
+        .collect::<Vec<_>>();
+    if loading.is_empty() {
+        return;
+    }
+
+    // The listeners are removed again once every image loaded or failed to
+    let handler = Rc::new(RefCell::new(None::<Closure<dyn Fn()>>));
+    let remaining = Cell::new(loading.len());
+
+    let last_scroll_top = Rc::new(Cell::new(scroll_top));
+    let images = loading.clone();
+    let handler_moved = handler.clone();
+    let callback = Closure::<dyn Fn()>::new(move || {
+        if element.scroll_top() == last_scroll_top.get() {
+            last_scroll_top.set(scroll_into_view(&element, &range, context));
+        }
+
+        remaining.set(remaining.get().saturating_sub(1));
+        if remaining.get() > 0 {
+            return;
+        }
+
+        let Some(callback) = handler_moved.borrow_mut().take() else {
+            return;
+        };
+        for image in &images {
+            for event in IMAGE_EVENTS {
+                image
+                    .remove_event_listener_with_callback(event, callback.as_ref().unchecked_ref())
+                    .ok();
+            }
+        }
+        // The callback is still running, so it can only be dropped afterwards
+        wasm_bindgen_futures::spawn_local(async move { drop(callback) });
+    });
+
+    for image in loading {
+        for event in IMAGE_EVENTS {
+            image
+                .add_event_listener_with_callback(event, callback.as_ref().unchecked_ref())
+                .ok();
+        }
+    }
+
+    *handler.borrow_mut() = Some(callback);
+}
+
+/// Scrolls to the position a jump asked for, once the chapter is rendered,
+/// and keeps it there while images load.
+fn apply_pending_position(read_state: UseRef<Option<ReaderState>>) {
+    let window = web_sys::window().expect("should have window");
 
     let callback = Closure::<dyn Fn()>::new(move || {
-        if let Some(read_state) = read_state.read().as_ref() {
-            log::info!("Calling apply");
-            read_state.apply_scroll();
+        let Some(pending) = read_state
+            .with_mut(|state| state.as_mut().and_then(ReaderState::take_pending_position))
+        else {
+            return;
+        };
+
+        let window = web_sys::window().expect("should have window");
+        let document = window.document().expect("should have document");
+        let Some(element) = document.get_element_by_id("reader-scroll") else {
+            return;
+        };
+
+        // Keep a bit of the text before a jump target visible for context
+        let is_match = matches!(pending, PendingPosition::Match { .. });
+        let (offset, len, context) = match pending {
+            PendingPosition::Offset(offset) => (Some(offset), 1, element.client_height() / 4),
+            PendingPosition::Match { offset, len } => {
+                (Some(offset), len, element.client_height() / 4)
+            }
+            PendingPosition::Cfi(cfi) => (cfi.char_offset(scan::READER_CONTENT_ID), 1, 0),
+        };
+
+        let Some(range) =
+            offset.and_then(|offset| scan::offset_range(scan::READER_CONTENT_ID, offset, len))
+        else {
+            log::warn!("Cannot find position {offset:?} in chapter");
+            return;
+        };
+
+        if is_match {
+            highlight::set(highlight::MATCH, &[range.clone()]);
         }
+
+        let scroll_top = scroll_into_view(&element, &range, context);
+        keep_in_view(element, range, context, scroll_top);
     });
 
     window
@@ -181,22 +342,26 @@ pub fn view_component<'a>(cx: Scope<'a, ViewProps<'a>>) -> Element<'a> {
 
     let known_text = use_ref(cx, || text.clone());
     let processed_text = use_ref(cx, || None);
-    let apply_scroll_block = use_state(cx, || false);
 
     let resource_cache = use_ref(cx, HashMap::<String, Option<String>>::new);
 
-    let onselect = &cx.props.onselect;
-    let read_state = cx.props.read_state;
+    // Where the pointer last was over the book, or None if it left
+    let pointer = use_ref(cx, || None::<(f64, f64)>);
 
-    // Set scroll after everything is rendered
     use_future(cx, (), |()| {
-        let read_state = read_state.clone();
+        let pointer = pointer.clone();
 
         async move {
-            apply_current_scroll(read_state);
+            enable_shift_callback(pointer);
         }
     });
 
+    let onselect = &cx.props.onselect;
+    let onhover = &cx.props.onhover;
+    let onrangeselect = &cx.props.onrangeselect;
+    let scan_mode = cx.props.scan_mode;
+    let read_state = cx.props.read_state;
+
     text.map_or_else(
         || cx.render(rsx! {p{"No document"}}),
         |text| {
@@ -206,43 +371,34 @@ pub fn view_component<'a>(cx: Scope<'a, ViewProps<'a>>) -> Element<'a> {
                     .and_then(crate::read_state::ReaderState::get_current_path)
             });
 
-            // Because scroll application triggers the scroll callback
-            // Potential images will only load after the processing below has passed and rendered once
-            // Therefore, we need to update the scroll position afterwards,
-            // and we need to avoid the callback saving the wrong position.
-            // Therefore, we block the callback for both renders until things have settled.
-            if *apply_scroll_block.get() {
-                apply_scroll_block.set(false);
-                let read_state = read_state.clone();
-                let window = web_sys::window().expect("should have window");
-                let callback = Closure::<dyn Fn()>::new(move || {
-                    read_state.with_mut(|state| state.as_mut().map(|s| s.set_scoll_blocked(true)));
-                });
-
-                window
-                    .set_timeout_with_callback(callback.as_ref().unchecked_ref())
-                    .unwrap();
-                callback.forget();
-            }
-
             if processed_text.read().is_none() || known_text.read().as_ref() != Some(&text) {
                 if let Some(path) = path {
                     let body = process_text(&path, &text, resource_cache, read_state);
 
                     known_text.set(Some(text));
                     processed_text.set(Some(body));
-                    read_state.with_mut(|state| state.as_mut().map(|s| s.set_scoll_blocked(true)));
-                    apply_scroll_block.set(true);
+                    highlight::clear(highlight::MATCH);
                 }
             }
 
             let body = processed_text.read().as_ref().unwrap().clone();
 
+            if read_state.with(|state| {
+                state
+                    .as_ref()
+                    .map_or(false, ReaderState::has_pending_position)
+            }) {
+                apply_pending_position(read_state.clone());
+            }
+
             cx.render(rsx! {
                 div {
+                    id: scan::READER_CONTENT_ID,
                     // TODO: Properly sandbox / iframe
                     dangerous_inner_html: "{body}",
-                    onclick: |_| clicked(onselect)
+                    onclick: |_| clicked(onselect, onrangeselect),
+                    onmousemove: move |evt| hovered(&evt, scan_mode, onhover, pointer),
+                    onmouseleave: move |_| *pointer.write_silent() = None,
                 }
             })
 
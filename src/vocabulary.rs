@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+use crate::{
+    analysis::{self, AnalysisState},
+    export, mining,
+    read_state::ReaderState,
+};
+
+// Rendering thousands of rows at once makes the panel sluggish
+const PAGE_SIZE: usize = 100;
+
+fn export_vocabulary(
+    analysis: &AnalysisState,
+    chapter: Option<usize>,
+    unknown_only: bool,
+    known: &HashSet<String>,
+) {
+    let AnalysisState::Done(result) = analysis else {
+        return;
+    };
+
+    let header = [
+        "Expression",
+        "Reading",
+        "Count",
+        "Chapter",
+        "Sentence",
+        "Known",
+    ]
+    .map(ToString::to_string)
+    .to_vec();
+    let rows = result
+        .vocabulary(chapter)
+        .into_iter()
+        .filter(|w| !unknown_only || !known.contains(&w.expression))
+        .map(|w| {
+            let is_known = known.contains(&w.expression);
+            let sentence = result.first_sentence(&w);
+            vec![
+                w.expression,
+                w.reading,
+                w.count.to_string(),
+                (w.first_page + 1).to_string(),
+                sentence,
+                is_known.to_string(),
+            ]
+        });
+
+    export::download(
+        &format!("{} vocabulary.csv", result.book),
+        "text/csv",
+        &export::csv(std::iter::once(header).chain(rows)),
+    );
+}
+
+#[derive(Props)]
+pub struct VocabularyProps<'a> {
+    analysis: &'a UseRef<AnalysisState>,
+    read_state: &'a UseRef<Option<ReaderState>>,
+    db: &'a UseRef<Option<yomi_dict::IndexedDB>>,
+    reasons: &'a UseState<yomi_dict::Reasons>,
+    known: &'a UseRef<HashSet<String>>,
+}
+
+pub fn vocabulary_component<'a>(cx: Scope<'a, VocabularyProps<'a>>) -> Element<'a> {
+    let analysis = cx.props.analysis;
+    let read_state = cx.props.read_state;
+    let known = cx.props.known;
+
+    // Chapter to list the words of, or the whole book
+    let chapter = use_state(cx, || None::<usize>);
+    let unknown_only = use_state(cx, || true);
+    let shown = use_state(cx, || PAGE_SIZE);
+    let mined = use_ref(cx, HashSet::<String>::new);
+
+    let current_book = read_state.with(|state| state.as_ref().map(ReaderState::get_title));
+
+    let start = move |_: MouseEvent| {
+        analysis::start(read_state, cx.props.db, cx.props.reasons, known, analysis);
+    };
+
+    let state = analysis.read();
+    let result = match &*state {
+        AnalysisState::Running(current, total) => {
+            let current = current + 1;
+            return cx.render(rsx!(p{
+                class: "mt-2",
+
+                "Analyzing chapter {current}/{total}"
+            }));
+        }
+        AnalysisState::Done(result) if Some(&result.book) == current_book.as_ref() => result,
+        AnalysisState::Done(_) | AnalysisState::Idle => {
+            return cx.render(rsx!(div{
+                class: "flex flex-col gap-2 mt-2",
+
+                p{ "Find the words used in this book to study them before reading." }
+                button{
+                    class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                    onclick: start,
+
+                    "Analyze book"
+                }
+            }));
+        }
+    };
+
+    let known_set = known.read();
+    let words = result.vocabulary(*chapter.get());
+    let total = words.len();
+    let filtered_count = words
+        .iter()
+        .filter(|w| !*unknown_only.get() || !known_set.contains(&w.expression))
+        .count();
+    let unknown_count = words
+        .iter()
+        .filter(|w| !known_set.contains(&w.expression))
+        .count();
+    let more = filtered_count > *shown.get();
+
+    let chapter_value = chapter.get().map_or_else(String::new, |c| c.to_string());
+
+    cx.render(rsx! {
+        div{
+            class: "flex flex-wrap gap-2 mt-2",
+
+            select{
+                class: "flex-1 min-w-0 bg-gray-100 rounded p-1",
+
+                value: "{chapter_value}",
+                onchange: move |evt: FormEvent| {
+                    chapter.set(evt.value.parse().ok());
+                    shown.set(PAGE_SIZE);
+                },
+
+                option{ value: "", "Whole book" }
+                result.chapters.iter().map(|c| rsx!(option{
+                    key: "{c.page}",
+                    value: "{c.page}",
+
+                    "{c.title}"
+                }))
+            }
+
+            label{
+                class: "flex items-center gap-1 text-sm",
+
+                input{
+                    r#type: "checkbox",
+                    checked: "{unknown_only}",
+                    onchange: move |evt: FormEvent| unknown_only.set(evt.value == "true"),
+                }
+
+                "Unknown only"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: move |_| export_vocabulary(
+                    &analysis.read(),
+                    *chapter.get(),
+                    *unknown_only.get(),
+                    &known.read(),
+                ),
+
+                "Export CSV"
+            }
+
+            button{
+                class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded",
+
+                onclick: start,
+
+                "Analyze again"
+            }
+        }
+
+        p{
+            class: "text-sm text-gray-600 my-1",
+
+            "{total} different words, {unknown_count} of them unknown"
+        }
+
+        ul{
+            class: "list-none",
+
+            words
+                .iter()
+                .filter(|w| !*unknown_only.get() || !known_set.contains(&w.expression))
+                .take(*shown.get())
+                .map(|w| {
+                    let is_known = known_set.contains(&w.expression);
+                    let status = if is_known { "known" } else { "unknown" };
+                    let is_mined = mined.read().contains(&w.expression);
+                    let chapter_title = result.chapter(w.first_page).map_or("", |c| c.title.as_str());
+                    let count = w.count;
+                    let (page, offset) = (w.first_page, w.first_offset);
+                    let sentence = result.first_sentence(w);
+                    let (expression, reading, mined_sentence) =
+                        (w.expression.clone(), w.reading.clone(), sentence.clone());
+                    let book = result.book.clone();
+
+                    rsx!(li{
+                        key: "{w.expression}",
+                        class: "border-b py-1",
+
+                        h2{
+                            class: "text-lg font-medium inline-block mr-2",
+
+                            ruby {
+                                "{w.expression}"
+                                rt{ "{w.reading}" }
+                            }
+                        }
+
+                        span{ class: "text-sm text-gray-600 mr-2", "×{count} · {status}" }
+
+                        button{
+                            class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300",
+
+                            onclick: move |_| read_state.with_mut(|state| {
+                                if let Some(state) = state.as_mut() {
+                                    state.jump_to(page, Some(offset));
+                                }
+                            }),
+
+                            "Go to"
+                        }
+
+                        button{
+                            class: "text-sm rounded-full m-1 px-2 bg-gray-200 hover:bg-gray-300 disabled:opacity-50",
+                            disabled: "{is_mined}",
+
+                            onclick: move |_| {
+                                mined.with_mut(|m| m.insert(expression.clone()));
+
+                                let db = cx.props.db.clone();
+                                let reasons = cx.props.reasons.clone();
+                                let word = (expression.clone(), reading.clone(), mined_sentence.clone(), book.clone());
+                                wasm_bindgen_futures::spawn_local(async move {
+                                    let (expression, reading, sentence, book) = word;
+                                    mining::mine_word(expression, reading, sentence, book, &db, reasons.get()).await;
+                                });
+                            },
+
+                            "Mine"
+                        }
+
+                        p{
+                            class: "text-sm text-gray-600",
+
+                            "First in {chapter_title}: {sentence}"
+                        }
+                    })
+                })
+        }
+
+        more.then(|| rsx!(button{
+            class: "bg-gray-100 hover:bg-gray-200 py-1 px-4 rounded my-2",
+
+            onclick: move |_| shown.modify(|s| s + PAGE_SIZE),
+
+            "Show more ({filtered_count})"
+        }))
+    })
+}